@@ -1,12 +1,59 @@
 use super::{html_props::HtmlProps, template::HtmlTemplate};
 use anyhow::Result;
-use std::{fs::File, io::Write, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
-const LANG_TAG: &str = "%LANG%";
-const HEAD_TAG: &str = "%HEAD%";
-const BODY_TAG: &str = "%BODY%";
-const SCRIPTS_TAG: &str = "%SCRIPTS%";
-const STYLES_TAG: &str = "%STYLES%";
+pub const LANG_SLOT: &str = "LANG";
+pub const HEAD_SLOT: &str = "HEAD";
+pub const BODY_SLOT: &str = "BODY";
+pub const SCRIPTS_SLOT: &str = "SCRIPTS";
+pub const STYLES_SLOT: &str = "STYLES";
+
+/// Slots a document template must contain to be able to render a page.
+pub const REQUIRED_SLOTS: [&str; 4] = [HEAD_SLOT, STYLES_SLOT, BODY_SLOT, SCRIPTS_SLOT];
+
+/// The id of the `<script>` element that carries the hydration data of a page.
+pub const DATA_SCRIPT_ID: &str = "__METASSR_DATA__";
+
+/// Escapes a serialized JSON document so it can be safely embedded inside a `<script>` element.
+///
+/// `<`, `>` and `&` can only appear inside JSON strings, so replacing them with their unicode
+/// escapes keeps the document valid while making `</script>` and `<!--` impossible to inject.
+/// Line and paragraph separators are escaped too, as some engines reject them in scripts.
+fn escape_json_for_html(json: &str) -> String {
+    let mut escaped = String::with_capacity(json.len());
+    for c in json.chars() {
+        match c {
+            '<' => escaped.push_str("\\u003c"),
+            '>' => escaped.push_str("\\u003e"),
+            '&' => escaped.push_str("\\u0026"),
+            '\u{2028}' => escaped.push_str("\\u2028"),
+            '\u{2029}' => escaped.push_str("\\u2029"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns the preload hint of a file, fetched as what its extension says it is.
+/// Fonts are always fetched in CORS mode, so the hint has to be too for the browser to reuse it.
+/// Scripts are fetched as classic scripts, like the chunks the bundler loads on demand.
+fn preload_link(path: &Path) -> String {
+    let href = path.display();
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext @ ("woff" | "woff2")) => format!(
+            "<link rel=\"preload\" href=\"{href}\" as=\"font\" type=\"font/{ext}\" crossorigin>"
+        ),
+        Some("png" | "jpg" | "jpeg" | "gif" | "webp" | "avif" | "svg") => {
+            format!("<link rel=\"preload\" href=\"{href}\" as=\"image\">")
+        }
+        _ => format!("<link rel=\"preload\" href=\"{href}\" as=\"script\">"),
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct HtmlOutput(String);
@@ -40,14 +87,41 @@ impl HtmlBuilder {
     }
 
     pub fn generate(&self) -> HtmlOutput {
+        // The data script must come before the page scripts, so it's ready once hydration starts.
+        let data = match &self.props.data {
+            Some(data) => format!(
+                "<script type=\"application/json\" id=\"{DATA_SCRIPT_ID}\">{}</script>",
+                escape_json_for_html(&data.to_string())
+            ),
+            None => String::new(),
+        };
+
         let scripts = self
             .props
             .scripts
             .iter()
-            .map(|path| format!("<script src=\"{}\"></script>", path.display()))
+            .map(|path| {
+                format!(
+                    "<script type=\"module\" src=\"{}\"></script>",
+                    path.display()
+                )
+            })
             .collect::<Vec<String>>()
             .join("");
 
+        // Preload hints follow the head tags, so the browser fetches the page's scripts, its
+        // chunks and fonts in parallel instead of discovering them one file at a time. The
+        // scripts are modules, which only module preloads are reused for.
+        let preloads = self
+            .props
+            .scripts
+            .iter()
+            .map(|path| format!("<link rel=\"modulepreload\" href=\"{}\">", path.display()))
+            .chain(self.props.preloads.iter().map(|path| preload_link(path)))
+            .collect::<Vec<String>>()
+            .join("");
+        let head = self.props.head.clone() + &preloads;
+
         let styles = self
             .props
             .styles
@@ -56,24 +130,33 @@ impl HtmlBuilder {
             .collect::<Vec<String>>()
             .join("");
 
-        HtmlOutput::from(
-            &self
-                .template
-                .to_string()
-                .replace(LANG_TAG, &self.props.lang)
-                .replace(HEAD_TAG, &self.props.head)
-                .replace(BODY_TAG, &self.props.body)
-                .replace(SCRIPTS_TAG, &scripts)
-                .replace(STYLES_TAG, &styles),
-        )
+        let scripts = data + &scripts;
+
+        // Extra slots can't override the fixed ones.
+        let mut values: HashMap<&str, &str> = self
+            .props
+            .slots
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        values.extend([
+            (LANG_SLOT, self.props.lang.as_str()),
+            (HEAD_SLOT, head.as_str()),
+            (BODY_SLOT, self.props.body.as_str()),
+            (SCRIPTS_SLOT, scripts.as_str()),
+            (STYLES_SLOT, styles.as_str()),
+        ]);
+
+        HtmlOutput::from(&self.template.render(&values))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{html_props::HtmlProps, template::HtmlTemplate};
+    use serde_json::json;
 
-    use super::HtmlBuilder;
+    use super::{HtmlBuilder, DATA_SCRIPT_ID};
 
     #[test]
     fn generating_html() {
@@ -93,4 +176,92 @@ mod tests {
         let html = HtmlBuilder::new(HtmlTemplate::default(), props).generate();
         println!("{html:?}")
     }
+
+    #[test]
+    fn embedding_escaped_data() {
+        let props = HtmlProps::new()
+            .lang("en")
+            .scripts(vec!["main.js".to_owned()])
+            .styles(vec![])
+            .data(json!({ "title": "</script><script>alert(1)</script> & more" }))
+            .build();
+        let html = HtmlBuilder::new(HtmlTemplate::default(), props)
+            .generate()
+            .to_string();
+
+        assert!(html.contains(&format!(
+            "<script type=\"application/json\" id=\"{DATA_SCRIPT_ID}\">"
+        )));
+        assert!(html.contains(r#"{"title":"\u003c/script\u003e\u003cscript\u003ealert(1)\u003c/script\u003e \u0026 more"}"#));
+        assert!(!html.contains("<script>alert(1)"));
+
+        // Hydration data has to be available before the page scripts run.
+        let data_at = html.find(DATA_SCRIPT_ID).unwrap();
+        assert!(
+            data_at
+                < html
+                    .find("<script type=\"module\" src=\"main.js\">")
+                    .unwrap()
+        );
+    }
+
+    #[test]
+    fn preloading_scripts() {
+        let props = HtmlProps::new()
+            .head("<title>Home</title>")
+            .scripts(vec!["vendor.js".to_owned(), "main.js".to_owned()])
+            .styles(vec!["style.css".to_owned()])
+            .preloads(vec!["chunks/chart.js".to_owned()])
+            .build();
+        let template = HtmlTemplate::new("<head>%HEAD%%STYLES%</head><body>%BODY%%SCRIPTS%</body>");
+        let html = HtmlBuilder::new(template, props).generate().to_string();
+
+        assert_eq!(
+            html,
+            "<head><title>Home</title>\
+             <link rel=\"modulepreload\" href=\"vendor.js\">\
+             <link rel=\"modulepreload\" href=\"main.js\">\
+             <link rel=\"preload\" href=\"chunks/chart.js\" as=\"script\">\
+             <link rel=\"stylesheet\" href=\"style.css\"></head>\
+             <body><script type=\"module\" src=\"vendor.js\"></script>\
+             <script type=\"module\" src=\"main.js\"></script></body>"
+        );
+    }
+
+    #[test]
+    fn preloading_fonts() {
+        let props = HtmlProps::new()
+            .scripts(vec!["main.js".to_owned()])
+            .styles(vec![])
+            .preloads(vec!["inter.woff2".to_owned()])
+            .build();
+        let template = HtmlTemplate::new("<head>%HEAD%%STYLES%</head><body>%BODY%%SCRIPTS%</body>");
+        let html = HtmlBuilder::new(template, props).generate().to_string();
+
+        assert!(html.contains("<link rel=\"modulepreload\" href=\"main.js\">"));
+        assert!(html.contains(
+            "<link rel=\"preload\" href=\"inter.woff2\" as=\"font\" type=\"font/woff2\" crossorigin>"
+        ));
+    }
+
+    #[test]
+    fn user_content_cannot_trigger_slots() {
+        let props = HtmlProps::new()
+            .head("<title>%BODY%</title>")
+            .body("<p>%SCRIPTS% and %ANALYTICS%</p>")
+            .scripts(vec!["main.js".to_owned()])
+            .styles(vec![])
+            .slot("ANALYTICS", "<script src=\"analytics.js\"></script>")
+            .build();
+        let template =
+            HtmlTemplate::new("<head>%HEAD%</head><body>%BODY%%SCRIPTS%%ANALYTICS%</body>");
+        let html = HtmlBuilder::new(template, props).generate().to_string();
+
+        assert_eq!(
+            html,
+            "<head><title>%BODY%</title><link rel=\"modulepreload\" href=\"main.js\"></head>\
+             <body><p>%SCRIPTS% and %ANALYTICS%</p>\
+             <script type=\"module\" src=\"main.js\"></script><script src=\"analytics.js\"></script></body>"
+        );
+    }
 }
@@ -1,4 +1,6 @@
+use serde_json::Value;
 use std::{
+    collections::HashMap,
     marker::Sized,
     path::{Path, PathBuf},
 };
@@ -11,6 +13,13 @@ pub struct HtmlProps {
     pub body: String,
     pub scripts: Vec<PathBuf>,
     pub styles: Vec<PathBuf>,
+    /// Files the browser should start fetching early, such as the chunks loaded by `scripts`
+    /// and the fonts used by `styles`.
+    pub preloads: Vec<PathBuf>,
+    /// Props computed on the server, embedded in the page so the client hydrates with the same data.
+    pub data: Option<Value>,
+    /// Values for template slots beyond the fixed ones (`LANG`, `HEAD`, `BODY`, `SCRIPTS` and `STYLES`).
+    pub slots: HashMap<String, String>,
 }
 
 impl HtmlProps {
@@ -26,6 +35,9 @@ pub struct HtmlPropsBuilder {
     body: Option<String>,
     scripts: Option<Vec<String>>,
     styles: Option<Vec<String>>,
+    preloads: Vec<String>,
+    data: Option<Value>,
+    slots: HashMap<String, String>,
 }
 
 impl HtmlPropsBuilder {
@@ -36,6 +48,9 @@ impl HtmlPropsBuilder {
             body: None,
             scripts: None,
             styles: None,
+            preloads: vec![],
+            data: None,
+            slots: HashMap::new(),
         }
     }
     pub fn lang<S: ToString + ?Sized>(mut self, lang: &S) -> Self {
@@ -58,6 +73,18 @@ impl HtmlPropsBuilder {
         self.styles = Some(styles);
         self
     }
+    pub fn preloads(mut self, preloads: Vec<String>) -> Self {
+        self.preloads = preloads;
+        self
+    }
+    pub fn data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+    pub fn slot<S: ToString + ?Sized>(mut self, name: &str, content: &S) -> Self {
+        self.slots.insert(name.to_string(), content.to_string());
+        self
+    }
     pub fn build(&self) -> HtmlProps {
         HtmlProps {
             lang: self.lang.as_ref().unwrap_or(&String::new()).to_owned(),
@@ -77,6 +104,9 @@ impl HtmlPropsBuilder {
                 .iter()
                 .map(|p| Path::new(p).to_path_buf())
                 .collect(),
+            preloads: self.preloads.iter().map(PathBuf::from).collect(),
+            data: self.data.clone(),
+            slots: self.slots.clone(),
         }
     }
 }
@@ -1,23 +1,192 @@
-use std::fmt::Display;
+use anyhow::{anyhow, Result};
+use std::{collections::HashMap, fmt::Display, sync::OnceLock};
+
+use crate::builder::REQUIRED_SLOTS;
 
 static DEFAULT_TEMPLATE: &str = include_str!("./default.html");
 
-pub struct HtmlTemplate(String);
+/// Wraps a slot name in a template, e.g. `%BODY%`.
+const SLOT_DELIMITER: char = '%';
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Slot(String),
+}
+
+/// An HTML document template, compiled once into text and slot segments.
+///
+/// Slots are written as `%NAME%`, where `NAME` is made of uppercase letters, digits and
+/// underscores. Filling a template is a single pass over its segments, so content put into a
+/// slot is never scanned for placeholders again.
+#[derive(Debug, Clone)]
+pub struct HtmlTemplate(Vec<Segment>);
 
 impl HtmlTemplate {
     pub fn new(template: &str) -> Self {
-        Self(template.to_owned())
+        let mut segments = vec![];
+        let mut text = String::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find(SLOT_DELIMITER) {
+            text.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+
+            match after.find(SLOT_DELIMITER) {
+                Some(end) if is_slot_name(&after[..end]) => {
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Slot(after[..end].to_string()));
+                    rest = &after[end + 1..];
+                }
+                // Not a slot, keep the delimiter as text.
+                _ => {
+                    text.push(SLOT_DELIMITER);
+                    rest = after;
+                }
+            }
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+
+        Self(segments)
+    }
+
+    /// Returns the names of the slots used in the template, in order of appearance.
+    pub fn slots(&self) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Slot(name) => Some(name.as_str()),
+                Segment::Text(_) => None,
+            })
+            .collect()
+    }
+
+    /// Returns `true` if the template contains the slot `name`.
+    pub fn has_slot(&self, name: &str) -> bool {
+        self.slots().contains(&name)
+    }
+
+    /// Checks that the template contains every slot needed to render a page.
+    pub fn validate(&self) -> Result<()> {
+        let missing: Vec<String> = REQUIRED_SLOTS
+            .iter()
+            .filter(|slot| !self.has_slot(slot))
+            .map(|slot| format!("{SLOT_DELIMITER}{slot}{SLOT_DELIMITER}"))
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "Missing required slots: {}. Every document must contain {}.",
+                missing.join(", "),
+                REQUIRED_SLOTS
+                    .map(|slot| format!("{SLOT_DELIMITER}{slot}{SLOT_DELIMITER}"))
+                    .join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fills the template slots in one pass. Slots without a value are rendered empty.
+    pub fn render(&self, values: &HashMap<&str, &str>) -> String {
+        let mut output = String::with_capacity(
+            self.0
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Text(text) => text.len(),
+                    Segment::Slot(name) => values.get(name.as_str()).map_or(0, |v| v.len()),
+                })
+                .sum(),
+        );
+
+        for segment in self.0.iter() {
+            match segment {
+                Segment::Text(text) => output.push_str(text),
+                Segment::Slot(name) => {
+                    if let Some(value) = values.get(name.as_str()) {
+                        output.push_str(value)
+                    }
+                }
+            }
+        }
+        output
     }
 }
 
+fn is_slot_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
 impl Default for HtmlTemplate {
     fn default() -> Self {
-        Self(DEFAULT_TEMPLATE.to_string())
+        static COMPILED: OnceLock<HtmlTemplate> = OnceLock::new();
+        COMPILED.get_or_init(|| Self::new(DEFAULT_TEMPLATE)).clone()
     }
 }
 
 impl Display for HtmlTemplate {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        for segment in self.0.iter() {
+            match segment {
+                Segment::Text(text) => write!(f, "{text}")?,
+                Segment::Slot(name) => write!(f, "{SLOT_DELIMITER}{name}{SLOT_DELIMITER}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiling_template() {
+        let template = HtmlTemplate::new("<p>100% %BODY% %lower% 50%</p>%FOOTER_2%");
+
+        assert_eq!(template.slots(), vec!["BODY", "FOOTER_2"]);
+        assert_eq!(
+            template.to_string(),
+            "<p>100% %BODY% %lower% 50%</p>%FOOTER_2%"
+        );
+    }
+
+    #[test]
+    fn rendering_is_single_pass() {
+        let template = HtmlTemplate::new("<body>%BODY%%SCRIPTS%</body>");
+        let values = HashMap::from([
+            ("BODY", "<p>%SCRIPTS%</p>"),
+            ("SCRIPTS", "<script></script>"),
+        ]);
+
+        assert_eq!(
+            template.render(&values),
+            "<body><p>%SCRIPTS%</p><script></script></body>"
+        );
+    }
+
+    #[test]
+    fn validating_template() {
+        assert!(HtmlTemplate::default().validate().is_ok());
+
+        let err = HtmlTemplate::new("<head>%HEAD%%STYLES%</head><body>%BODY%</body>")
+            .validate()
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Missing required slots: %SCRIPTS%."));
+    }
+
+    #[test]
+    fn missing_slot_values_are_empty() {
+        let template = HtmlTemplate::new("<head>%HEAD%</head>");
+        assert_eq!(template.render(&HashMap::new()), "<head></head>");
     }
 }
@@ -0,0 +1,218 @@
+use anyhow::Result;
+use metassr_bundler::{BundleOutput, BUNDLER_CONFIG, DEPENDENCY_FILES, STYLE_CONFIGS};
+use metassr_utils::hash::{content_hash, file_hash};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The name of the build graph, in the cache directory of the `dist/` directory.
+pub const BUILD_GRAPH: &str = "build-graph.json";
+
+/// `BuildGraph` records the inputs of each page built by the last build, with their content
+/// hashes, so the next build only rebuilds the pages whose inputs changed.
+///
+/// The inputs of a page are the project's files bundled into its client and server bundles
+/// (the page itself, `_app`, its layouts and the modules they import), plus `_head` and
+/// `_document`. Everything shared by all pages (build options, MetaSSR version, dependencies,
+/// bundler configuration) is summed up in a single global hash; when it changes, every page is
+/// rebuilt.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildGraph {
+    pub global: String,
+    /// The inputs of each page, by route (e.g. `blog/index.tsx`), mapped to their hashes.
+    pub pages: BTreeMap<String, BTreeMap<PathBuf, String>>,
+}
+
+impl BuildGraph {
+    /// Creates an empty graph for a build with the given global hash (see [`global_hash`]).
+    pub fn new(global: String) -> Self {
+        Self {
+            global,
+            pages: BTreeMap::new(),
+        }
+    }
+
+    /// Loads the graph of the last build from `dist/cache`. A missing or unreadable graph is
+    /// empty, so everything is rebuilt.
+    pub fn load<S: AsRef<Path> + ?Sized>(dist: &S) -> Self {
+        fs::read_to_string(dist.as_ref().join("cache").join(BUILD_GRAPH))
+            .ok()
+            .and_then(|graph| serde_json::from_str(&graph).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the graph to `dist/cache`.
+    pub fn write<S: AsRef<Path> + ?Sized>(&self, dist: &S) -> Result<()> {
+        let cache = dist.as_ref().join("cache");
+        fs::create_dir_all(&cache)?;
+        fs::write(cache.join(BUILD_GRAPH), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Returns the pages among `pages` (routes mapped to their files) that have to be rebuilt,
+    /// or `None` if the whole project has to be, because the global hash changed or pages
+    /// were removed since the last build.
+    pub fn stale_pages(
+        &self,
+        global: &str,
+        pages: &HashMap<String, PathBuf>,
+    ) -> Option<HashSet<String>> {
+        if self.global != global || self.pages.keys().any(|page| !pages.contains_key(page)) {
+            return None;
+        }
+
+        let mut hashes: HashMap<&Path, Option<String>> = HashMap::new();
+        let stale = pages
+            .keys()
+            .filter(|page| match self.pages.get(*page) {
+                Some(inputs) => inputs.iter().any(|(input, hash)| {
+                    let current = hashes.entry(input).or_insert_with(|| file_hash(input).ok());
+                    current.as_ref() != Some(hash)
+                }),
+                None => true,
+            })
+            .cloned()
+            .collect();
+        Some(stale)
+    }
+
+    /// Records the inputs of the built `pages`, found in the bundles of `outputs`, along with
+    /// the `shared` inputs of every page.
+    ///
+    /// A page's bundles are the entries whose modules include the page's file. Files in
+    /// `dist` (such as the generated hydration and render scripts) aren't inputs.
+    pub fn record(
+        &mut self,
+        pages: &HashMap<String, PathBuf>,
+        outputs: &[&BundleOutput],
+        shared: &[PathBuf],
+        dist: &Path,
+    ) -> Result<()> {
+        let dist = dist.canonicalize()?;
+        for (page, path) in pages {
+            let path = path.canonicalize()?;
+            let modules = outputs
+                .iter()
+                .flat_map(|output| output.entrypoints.values())
+                .filter(|entry| entry.modules.contains(&path))
+                .flat_map(|entry| entry.modules.iter());
+
+            let mut inputs = BTreeMap::new();
+            for input in modules.chain(shared).chain([&path]) {
+                if input.starts_with(&dist) || inputs.contains_key(input) {
+                    continue;
+                }
+                inputs.insert(input.clone(), file_hash(input)?);
+            }
+            self.pages.insert(page.clone(), inputs);
+        }
+        Ok(())
+    }
+}
+
+/// Hashes what every page of the project in `root` depends on: `options` (e.g. the build mode
+/// and type), the MetaSSR version, the project's `package.json` and lockfiles, its stylesheet
+/// configurations (PostCSS and Tailwind), and its bundler configuration hook.
+pub fn global_hash<S: AsRef<Path> + ?Sized>(root: &S, options: &str) -> String {
+    let mut global = format!("{}\n{options}", env!("CARGO_PKG_VERSION"));
+    let files = DEPENDENCY_FILES.iter().chain(&STYLE_CONFIGS);
+    for file in files.chain([&BUNDLER_CONFIG]) {
+        if let Ok(hash) = file_hash(&root.as_ref().join(file)) {
+            global.push_str(&format!("\n{file}:{hash}"));
+        }
+    }
+    content_hash(global.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::temp_dir;
+    use metassr_bundler::Entrypoint;
+
+    #[test]
+    fn rebuilding_changed_pages() {
+        let root = temp_dir();
+        let dist = root.join("dist");
+        fs::create_dir_all(&dist).unwrap();
+        for file in [
+            "index.jsx",
+            "about.jsx",
+            "_app.jsx",
+            "_head.jsx",
+            "button.jsx",
+        ] {
+            fs::write(root.join(file), file).unwrap();
+        }
+        fs::write(dist.join("index.js"), "generated").unwrap();
+
+        let root = root.canonicalize().unwrap();
+        let dist = dist.canonicalize().unwrap();
+        let pages = HashMap::from([
+            ("index.jsx".to_string(), root.join("index.jsx")),
+            ("about.jsx".to_string(), root.join("about.jsx")),
+        ]);
+        let entry = |modules: &[&str]| Entrypoint {
+            modules: modules.iter().map(|m| root.join(m)).collect(),
+            ..Default::default()
+        };
+        let output = BundleOutput {
+            entrypoints: HashMap::from([
+                (
+                    "pages/index.js".to_string(),
+                    entry(&["dist/index.js", "index.jsx", "_app.jsx", "button.jsx"]),
+                ),
+                (
+                    "pages/about.js".to_string(),
+                    entry(&["about.jsx", "_app.jsx"]),
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        let global = global_hash(&root, "production");
+        let mut graph = BuildGraph::new(global.clone());
+        graph
+            .record(&pages, &[&output], &[root.join("_head.jsx")], &dist)
+            .unwrap();
+        assert_eq!(graph.pages["index.jsx"].len(), 4);
+        graph.write(&dist).unwrap();
+
+        let graph = BuildGraph::load(&dist);
+        assert_eq!(graph.stale_pages(&global, &pages), Some(HashSet::new()));
+
+        fs::write(root.join("button.jsx"), "changed").unwrap();
+        assert_eq!(
+            graph.stale_pages(&global, &pages),
+            Some(HashSet::from(["index.jsx".to_string()]))
+        );
+
+        fs::write(root.join("_head.jsx"), "changed").unwrap();
+        assert_eq!(graph.stale_pages(&global, &pages).unwrap().len(), 2);
+
+        assert_eq!(
+            graph.stale_pages(&global_hash(&root, "development"), &pages),
+            None
+        );
+        fs::write(
+            root.join(BUNDLER_CONFIG),
+            "module.exports = (config) => config;",
+        )
+        .unwrap();
+        assert_eq!(
+            graph.stale_pages(&global_hash(&root, "production"), &pages),
+            None
+        );
+
+        // Stylesheet configurations change the styles of every page
+        for file in ["postcss.config.js", "tailwind.config.ts"] {
+            let global = global_hash(&root, "production");
+            fs::write(root.join(file), "export default {};").unwrap();
+            assert_ne!(global_hash(&root, "production"), global);
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
-    shared::{APP_PATH_TAG, PAGE_PATH_TAG, ROOT_ID_TAG},
+    shared::{APP_PATH_TAG, LAYOUTS_IMPORTS_TAG, LAYOUTS_TAG, PAGE_PATH_TAG, ROOT_ID_TAG},
     traits::Generate,
+    utils::setup_layouts,
 };
 use anyhow::Result;
 use std::{ffi::OsStr, path::PathBuf};
@@ -11,17 +12,25 @@ const HYDRATED_FILE_TEMPLATE: &str = include_str!("../scripts/hydrate.js.templat
 pub struct Hydrator {
     app_path: PathBuf,
     page_path: PathBuf,
+    layouts: Vec<PathBuf>,
     root_id: String,
 }
 
 impl Hydrator {
-    pub fn new<'a, S>(app_path: &'a S, page_path: &'a S, root_id: &'a str) -> Self
+    /// `layouts` are the layouts wrapping the page, from the outermost to the innermost.
+    pub fn new<'a, S>(
+        app_path: &'a S,
+        page_path: &'a S,
+        layouts: &[PathBuf],
+        root_id: &'a str,
+    ) -> Self
     where
         S: AsRef<OsStr> + ?Sized,
     {
         Self {
             app_path: PathBuf::from(app_path),
             page_path: PathBuf::from(page_path),
+            layouts: layouts.to_vec(),
             root_id: root_id.to_string(),
         }
     }
@@ -30,6 +39,8 @@ impl Hydrator {
 impl Generate for Hydrator {
     type Output = String;
     fn generate(&self) -> Result<Self::Output> {
+        let (layouts_imports, layouts) = setup_layouts(&self.layouts, true)?;
+
         Ok(HYDRATED_FILE_TEMPLATE
             .replace(
                 APP_PATH_TAG,
@@ -39,6 +50,8 @@ impl Generate for Hydrator {
                 PAGE_PATH_TAG,
                 self.page_path.canonicalize()?.to_str().unwrap(),
             )
+            .replace(LAYOUTS_IMPORTS_TAG, &layouts_imports)
+            .replace(LAYOUTS_TAG, &layouts)
             .replace(ROOT_ID_TAG, &self.root_id))
     }
 }
@@ -49,7 +62,7 @@ mod tests {
     fn generate_hydrated_file() {
         println!(
             "{}",
-            Hydrator::new("src/_app.tsx", "src/pages/home.jsx", "root")
+            Hydrator::new("src/_app.tsx", "src/pages/home.jsx", &[], "root")
                 .generate()
                 .unwrap()
         );
@@ -3,18 +3,24 @@ use crate::utils::setup_page_path;
 use anyhow::{anyhow, Result};
 use hydrator::Hydrator;
 
-use metassr_bundler::WebBundler;
+use metassr_bundler::{
+    backends::Bundler,
+    options::{BundleOptions, Targets},
+    BundleOutput, BundleProfile, WebBundler,
+};
 use metassr_fs_analyzer::{
+    dist_dir::{ClientEntry, ClientManifest},
     src_dir::{special_entries, SourceDir},
     DirectoryAnalyzer,
 };
 use metassr_utils::cache_dir::CacheDir;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     ffi::OsStr,
     fs,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 pub mod hydrator;
@@ -22,6 +28,10 @@ pub mod hydrator;
 pub struct ClientBuilder {
     src_path: PathBuf,
     dist_path: PathBuf,
+    pages: Option<HashSet<String>>,
+    options: BundleOptions,
+    browser_targets: Targets,
+    backend: Option<Arc<dyn Bundler>>,
 }
 
 impl ClientBuilder {
@@ -42,27 +52,62 @@ impl ClientBuilder {
         Ok(Self {
             src_path,
             dist_path,
+            pages: None,
+            options: BundleOptions::default(),
+            browser_targets: Targets::default(),
+            backend: None,
         })
     }
+
+    /// Limits the build to `pages`, given as routes of the source directory pages
+    /// (e.g. `blog/index.tsx`). All pages are built by default.
+    pub fn pages(mut self, pages: HashSet<String>) -> Self {
+        self.pages = Some(pages);
+        self
+    }
+
+    /// Sets the build mode and source maps. Production ones are used by default.
+    pub fn options(mut self, options: BundleOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets the browsers the hydration scripts are built for. Their syntax is left as is by
+    /// default.
+    pub fn browser_targets(mut self, targets: Targets) -> Self {
+        self.browser_targets = targets;
+        self
+    }
+
+    /// Builds the hydration scripts with `backend` instead of the backend selected by the options.
+    pub fn backend(mut self, backend: Arc<dyn Bundler>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
 }
 
-impl Build for ClientBuilder {
-    type Output = ();
-    fn build(&self) -> Result<Self::Output> {
+impl ClientBuilder {
+    /// Generates the hydration scripts of the pages in the cache directory, returning them
+    /// as bundling targets.
+    pub(crate) fn targets(&self) -> Result<HashMap<String, String>> {
         let mut cache_dir = CacheDir::new(&format!("{}/cache", self.dist_path.display()))?;
         let src = SourceDir::new(&self.src_path).analyze()?;
 
-        let pages = src.pages();
+        let mut pages = src.pages();
+        if let Some(only) = &self.pages {
+            pages.retain(|page, _| only.contains(page));
+        }
         let (special_entries::App(app_path), _) = src.specials()?;
 
         for (page, page_path) in pages.iter() {
-            let hydrator = Hydrator::new(&app_path, page_path, "root").generate()?;
+            let layouts = src.layouts_for(page);
+            let hydrator = Hydrator::new(&app_path, page_path, &layouts, "root").generate()?;
             let page = setup_page_path(page, "js");
 
             cache_dir.insert(&format!("pages/{}", page.display()), hydrator.as_bytes())?;
         }
 
-        let targets = cache_dir
+        Ok(cache_dir
             .entries_in_scope()
             .iter()
             .map(|(entry_name, path)| {
@@ -70,20 +115,89 @@ impl Build for ClientBuilder {
 
                 (entry_name.to_owned(), format!("{}", fullpath.display()))
             })
-            .collect::<HashMap<String, String>>();
+            .collect())
+    }
+
+    /// The bundler of the hydration scripts, from the targets returned by `targets`.
+    pub(crate) fn bundler<'a>(
+        &'a self,
+        targets: &'a HashMap<String, String>,
+    ) -> Result<WebBundler<'a>> {
+        let bundler = WebBundler::new(targets, &self.dist_path, BundleProfile::Client)?
+            .options(self.options)
+            .browser_targets(self.browser_targets.clone())
+            .cache(&self.dist_path.join("cache/rspack/client"));
+        Ok(match &self.backend {
+            Some(backend) => bundler.backend(Arc::clone(backend)),
+            None => bundler,
+        })
+    }
 
-        let bundler = WebBundler::new(&targets, &self.dist_path)?;
-        if let Err(e) = bundler.exec() {
-            return Err(anyhow!("Bundling failed: {e}"));
+    /// Records the bundled files of `output` in the client manifest, and removes the files
+    /// they replaced. Returns the updated manifest.
+    pub(crate) fn finish(&self, output: &BundleOutput) -> Result<ClientManifest> {
+        // A partial build only replaces the entries of the rebuilt pages.
+        let previous = ClientManifest::read(&self.dist_path)?.unwrap_or_default();
+        let mut manifest = match self.pages {
+            Some(_) => previous.clone(),
+            None => ClientManifest::default(),
+        };
+        for (entry, entrypoint) in &output.entrypoints {
+            manifest.entries.insert(
+                entry.clone(),
+                ClientEntry {
+                    files: entrypoint.files.iter().map(PathBuf::from).collect(),
+                    chunks: entrypoint.chunks.iter().map(PathBuf::from).collect(),
+                    assets: entrypoint.assets.iter().map(PathBuf::from).collect(),
+                },
+            );
         }
+        manifest.write(&self.dist_path)?;
+        clean_stale_files(&self.dist_path, &previous, &manifest)?;
+        Ok(manifest)
+    }
+}
+
+impl Build for ClientBuilder {
+    /// The bundled files and modules of each page.
+    type Output = BundleOutput;
+    fn build(&self) -> Result<Self::Output> {
+        let targets = self.targets()?;
+        // Bundling errors are returned as is, so their diagnostics reach the caller.
+        let output = self.bundler(&targets)?.exec()?;
+        self.finish(&output)?;
+        Ok(output)
+    }
+}
 
-        Ok(())
+/// Removes the files the `previous` manifest referenced that the current `manifest` doesn't
+/// anymore, along with their source maps (e.g. `index.js.1a2b3c4d.js` once
+/// `index.js.5e6f7a8b.js` replaced it). Only the files the client build emitted are removed,
+/// whatever their names, so files copied next to them are kept.
+fn clean_stale_files(
+    dist_path: &Path,
+    previous: &ClientManifest,
+    manifest: &ClientManifest,
+) -> Result<()> {
+    let referenced = manifest.files();
+    for file in previous.files().difference(&referenced) {
+        let path = dist_path.join(file);
+        let map = PathBuf::from(format!("{}.map", path.display()));
+        for path in [path, map] {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
     }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::temp_dir;
+    use std::collections::BTreeMap;
+
     #[test]
     fn client_builder() {
         ClientBuilder::new("../../tests/web-app", "../../tests/web-app/dist")
@@ -91,4 +205,73 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    #[test]
+    fn cleaning_stale_files() {
+        let dist = temp_dir();
+        let pages = dist.join("pages");
+        let chunks = dist.join("chunks");
+        fs::create_dir_all(&pages).unwrap();
+        fs::create_dir_all(&chunks).unwrap();
+        for file in [
+            "pages/index.js.1a2b3c4d.js",
+            "pages/index.js.1a2b3c4d.js.map",
+            "pages/index.js.5e6f7a8b.js",
+            "pages/index.js.5e6f7a8b.js.map",
+            "pages/about.js.9c0d1e2f.js",
+            "pages/index.html",
+            "pages/logo.deadbeef.png",
+            "chunks/vendor.3a4b5c6d.js",
+            "chunks/vendor.7e8f9a0b.js",
+            "chunks/v.20240101.js",
+        ] {
+            fs::write(dist.join(file), "").unwrap();
+        }
+
+        let entry = |files: &[&str]| ClientEntry {
+            files: files.iter().map(PathBuf::from).collect(),
+            chunks: vec![],
+            assets: vec![],
+        };
+        let previous = ClientManifest {
+            entries: BTreeMap::from([
+                (
+                    "pages/index.js".to_string(),
+                    entry(&["chunks/vendor.3a4b5c6d.js", "pages/index.js.1a2b3c4d.js"]),
+                ),
+                (
+                    "pages/about.js".to_string(),
+                    entry(&["chunks/vendor.3a4b5c6d.js", "pages/about.js.9c0d1e2f.js"]),
+                ),
+            ]),
+        };
+        let manifest = ClientManifest {
+            entries: BTreeMap::from([(
+                "pages/index.js".to_string(),
+                entry(&["chunks/vendor.7e8f9a0b.js", "pages/index.js.5e6f7a8b.js"]),
+            )]),
+        };
+        clean_stale_files(&dist, &previous, &manifest).unwrap();
+
+        let list = |dir: &Path| {
+            let mut files: Vec<String> = fs::read_dir(dir)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+                .collect();
+            files.sort();
+            files
+        };
+        // Files the previous build didn't emit are kept, even when their names look hashed
+        assert_eq!(
+            list(&pages),
+            vec![
+                "index.html",
+                "index.js.5e6f7a8b.js",
+                "index.js.5e6f7a8b.js.map",
+                "logo.deadbeef.png"
+            ]
+        );
+        assert_eq!(list(&chunks), vec!["v.20240101.js", "vendor.7e8f9a0b.js"]);
+        fs::remove_dir_all(dist).unwrap();
+    }
 }
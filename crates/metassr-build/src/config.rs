@@ -0,0 +1,191 @@
+use crate::images::ImageOptions;
+use anyhow::{anyhow, Result};
+use metassr_bundler::options::{Backend, BuildMode, SourceMaps, Targets};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{fs::read_to_string, path::Path};
+
+/// The project's configuration file, in the project root.
+pub const CONFIG_FILE: &str = "metassr.config.json";
+
+/// The project's build configuration, read from `metassr.config.json`.
+///
+/// ```json
+/// {
+///     "mode": "production",
+///     "sourceMaps": "hidden",
+///     "bundler": "rspack",
+///     "images": { "widths": [640, 1080, 1920], "formats": ["avif", "webp"] },
+///     "targets": ["> 0.5%", "not dead", "chrome >= 61"],
+///     "polyfills": true
+/// }
+/// ```
+///
+/// Every field is optional, and command line arguments take precedence over them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// The mode of `metassr build`, `production` or `development`.
+    pub mode: Option<BuildMode>,
+    /// The source maps of the client bundles, `none`, `hidden` or `full`.
+    /// Defaults to `hidden` in production and `full` in development.
+    pub source_maps: Option<SourceMaps>,
+    /// The bundler backend, `rspack` (the default) or `esbuild`.
+    pub bundler: Option<Backend>,
+    /// The widths and formats images are optimized into.
+    pub images: Option<ImageOptions>,
+    /// The browserslist queries of the browsers the client bundles support. Defaults to the
+    /// `browserslist` field of `package.json`.
+    pub targets: Option<Vec<String>>,
+    /// Whether core-js polyfills are injected into the client bundles, for the targeted
+    /// browsers. Disabled by default.
+    pub polyfills: Option<bool>,
+}
+
+impl Config {
+    /// Loads the configuration of the project in `root`. Projects without a configuration file
+    /// get the default one.
+    pub fn load<S: AsRef<Path> + ?Sized>(root: &S) -> Result<Self> {
+        let path = root.as_ref().join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        match serde_json::from_str(&read_to_string(&path)?) {
+            Ok(config) => Ok(config),
+            Err(e) => Err(anyhow!("Invalid configuration in {CONFIG_FILE}: {e}")),
+        }
+    }
+
+    /// Returns the browsers the client bundles of the project in `root` target in `mode`:
+    /// the configured ones, or the `browserslist` field of its `package.json`, which may list
+    /// queries by mode.
+    pub fn browser_targets<S: AsRef<Path> + ?Sized>(
+        &self,
+        root: &S,
+        mode: BuildMode,
+    ) -> Result<Targets> {
+        let browsers = match &self.targets {
+            Some(targets) => targets.clone(),
+            None => package_browserslist(root.as_ref(), mode)?,
+        };
+        Ok(Targets {
+            browsers,
+            polyfills: self.polyfills.unwrap_or_default(),
+        })
+    }
+}
+
+/// Reads the `browserslist` field of a project's `package.json`: a query, a list of queries,
+/// or lists by environment (e.g. `production` and `development`, falling back to `defaults`).
+fn package_browserslist(root: &Path, mode: BuildMode) -> Result<Vec<String>> {
+    let Ok(package) = read_to_string(root.join("package.json")) else {
+        return Ok(vec![]);
+    };
+    let package: Value =
+        serde_json::from_str(&package).map_err(|e| anyhow!("Invalid package.json: {e}"))?;
+
+    let field = match package.get("browserslist") {
+        Some(Value::Object(envs)) => envs.get(&mode.to_string()).or(envs.get("defaults")),
+        field => field,
+    };
+    match field {
+        None => Ok(vec![]),
+        Some(Value::String(query)) => Ok(query.split(',').map(|q| q.trim().to_owned()).collect()),
+        Some(Value::Array(queries)) => queries
+            .iter()
+            .map(|query| match query {
+                Value::String(query) => Ok(query.clone()),
+                _ => Err(anyhow!(
+                    "Invalid browserslist in package.json: {query} isn't a query"
+                )),
+            })
+            .collect(),
+        Some(field) => Err(anyhow!("Invalid browserslist in package.json: {field}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn loading_config() {
+        let root = temp_dir();
+        assert_eq!(Config::load(&root).unwrap(), Config::default());
+
+        fs::write(
+            root.join(CONFIG_FILE),
+            r#"{ "mode": "development", "sourceMaps": "none", "bundler": "esbuild", "images": { "widths": [480] } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Config::load(&root).unwrap(),
+            Config {
+                mode: Some(BuildMode::Development),
+                source_maps: Some(SourceMaps::None),
+                bundler: Some(Backend::Esbuild),
+                images: Some(ImageOptions {
+                    widths: vec![480],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+        );
+
+        fs::write(root.join(CONFIG_FILE), r#"{ "mode": "fast" }"#).unwrap();
+        assert!(Config::load(&root).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn browser_targets() {
+        let root = temp_dir();
+        let config = Config::default();
+        assert_eq!(
+            config
+                .browser_targets(&root, BuildMode::Production)
+                .unwrap(),
+            Targets::default()
+        );
+
+        fs::write(
+            root.join("package.json"),
+            r#"{ "browserslist": { "production": ["> 0.5%", "not dead"], "defaults": "last 1 chrome version" } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config
+                .browser_targets(&root, BuildMode::Production)
+                .unwrap()
+                .browsers,
+            vec!["> 0.5%", "not dead"]
+        );
+        assert_eq!(
+            config
+                .browser_targets(&root, BuildMode::Development)
+                .unwrap()
+                .browsers,
+            vec!["last 1 chrome version"]
+        );
+
+        fs::write(
+            root.join(CONFIG_FILE),
+            r#"{ "targets": ["chrome >= 61"], "polyfills": true }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Config::load(&root)
+                .unwrap()
+                .browser_targets(&root, BuildMode::Production)
+                .unwrap(),
+            Targets {
+                browsers: vec!["chrome >= 61".to_owned()],
+                polyfills: true,
+            }
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}
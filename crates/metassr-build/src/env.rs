@@ -0,0 +1,348 @@
+use anyhow::{anyhow, Result};
+use metassr_bundler::options::BuildMode;
+use metassr_fs_analyzer::dist_dir::ClientManifest;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, read_to_string},
+    path::Path,
+};
+use walkdir::WalkDir;
+
+/// The prefix of the variables inlined into the client bundles. Variables without it are only
+/// available on the server.
+pub const PUBLIC_PREFIX: &str = "METASSR_PUBLIC_";
+
+/// Secrets shorter than this aren't looked for in the client output, as short values (e.g.
+/// `true` or `3000`) would be found in unrelated code.
+const MIN_SECRET_LEN: usize = 8;
+
+/// The environment files of a project in `mode`, in the order they're loaded: variables of the
+/// later files override the ones of the earlier files.
+fn env_files(mode: BuildMode) -> [String; 3] {
+    [
+        ".env".to_owned(),
+        format!(".env.{mode}"),
+        ".env.local".to_owned(),
+    ]
+}
+
+/// Parses the content of an environment file: `KEY=value` lines, optionally prefixed with
+/// `export`, with `#` comments. Values may be quoted: double-quoted values support the `\n`,
+/// `\r`, `\t`, `\\` and `\"` escapes, and single-quoted ones are taken as they are.
+fn parse(content: &str, file: &str) -> Result<Vec<(String, String)>> {
+    let mut vars = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: &str| anyhow!("Invalid line in {file}:{}: {reason}", i + 1);
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(invalid("expected `KEY=value`"));
+        };
+        let key = key.trim();
+        let valid_key = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            return Err(invalid(&format!("invalid variable name `{key}`")));
+        }
+
+        let value = value.trim();
+        let value = if let Some(quoted) = value.strip_prefix('"') {
+            let mut unescaped = String::new();
+            let mut chars = quoted.chars();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => unescaped.push('\n'),
+                        Some('r') => unescaped.push('\r'),
+                        Some('t') => unescaped.push('\t'),
+                        Some(c) => unescaped.push(c),
+                        None => return Err(invalid("unterminated value")),
+                    },
+                    Some(c) => unescaped.push(c),
+                    None => return Err(invalid("unterminated value")),
+                }
+            }
+            unescaped
+        } else if let Some(quoted) = value.strip_prefix('\'') {
+            match quoted.split_once('\'') {
+                Some((quoted, _)) => quoted.to_owned(),
+                None => return Err(invalid("unterminated value")),
+            }
+        } else {
+            // Unquoted values end at their comment, if they have one
+            match value.split_once(" #") {
+                Some((value, _)) => value.trim_end().to_owned(),
+                None => value.to_owned(),
+            }
+        };
+        vars.push((key.to_owned(), value));
+    }
+    Ok(vars)
+}
+
+/// `Env` holds the environment variables of a project, loaded from its `.env`, `.env.{mode}`
+/// and `.env.local` files, in this order. Variables already set in the environment take
+/// precedence over the files.
+///
+/// Variables prefixed with [`PUBLIC_PREFIX`] are inlined into the bundles. The other ones are
+/// secrets, only available to the server at runtime.
+///
+/// # Example
+///
+/// ```no_run
+/// use metassr_build::env::Env;
+/// use metassr_bundler::options::BuildMode;
+///
+/// let env = Env::load(".", BuildMode::Production).unwrap();
+/// env.apply();
+/// for (key, value) in env.public() {
+///     println!("{key}={value}");
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Env {
+    vars: BTreeMap<String, String>,
+}
+
+impl Env {
+    /// Loads the environment files of the project in `root`, for `mode`. Missing files are
+    /// skipped.
+    pub fn load<S: AsRef<Path> + ?Sized>(root: &S, mode: BuildMode) -> Result<Self> {
+        let mut vars = BTreeMap::new();
+        for file in env_files(mode) {
+            let path = root.as_ref().join(&file);
+            if path.is_file() {
+                vars.extend(parse(&read_to_string(&path)?, &file)?);
+            }
+        }
+
+        // The environment overrides the files, and can set public variables of its own
+        for (key, value) in std::env::vars() {
+            if vars.contains_key(&key) || key.starts_with(PUBLIC_PREFIX) {
+                vars.insert(key, value);
+            }
+        }
+        Ok(Self { vars })
+    }
+
+    /// Returns every variable, by name.
+    pub fn vars(&self) -> &BTreeMap<String, String> {
+        &self.vars
+    }
+
+    /// Returns the variables inlined into the bundles, prefixed with [`PUBLIC_PREFIX`].
+    pub fn public(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.vars
+            .iter()
+            .filter(|(key, _)| key.starts_with(PUBLIC_PREFIX))
+    }
+
+    /// Returns the variables that must stay on the server.
+    pub fn secrets(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.vars
+            .iter()
+            .filter(|(key, _)| !key.starts_with(PUBLIC_PREFIX))
+    }
+
+    /// Sets the variables in the environment of the process, so the render scripts and loaders
+    /// read them from `process.env`. Variables already set are left as they are.
+    ///
+    /// Setting variables isn't thread-safe: call it before starting any thread, such as the
+    /// ones of an async runtime or of MetaCall.
+    pub fn apply(&self) {
+        for (key, value) in &self.vars {
+            if std::env::var_os(key).is_none() {
+                std::env::set_var(key, value);
+            }
+        }
+    }
+
+    /// Verifies that no secret reached the client output in `dist`: the client bundles, as
+    /// listed by their client manifest, with their source maps, and the generated HTML pages
+    /// (e.g. the static pages of an SSG build), along with the data they embed for hydration.
+    pub fn check_client_output<S: AsRef<Path> + ?Sized>(
+        &self,
+        dist: &S,
+        manifest: &ClientManifest,
+    ) -> Result<()> {
+        // A value shared with a public variable was published on purpose.
+        let public: HashSet<&String> = self.public().map(|(_, value)| value).collect();
+        let secrets: Vec<(&String, &String)> = self
+            .secrets()
+            .filter(|(_, value)| value.len() >= MIN_SECRET_LEN && !public.contains(value))
+            .collect();
+        if secrets.is_empty() {
+            return Ok(());
+        }
+
+        let dist = dist.as_ref();
+        let bundles = manifest.files().into_iter().flat_map(|file| {
+            let path = dist.join(file);
+            let map = path.with_file_name(format!(
+                "{}.map",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ));
+            [path, map]
+        });
+        // The cache holds the server side only
+        let pages = WalkDir::new(dist)
+            .into_iter()
+            .filter_entry(|entry| entry.path() != dist.join("cache"))
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry.file_type().is_file()
+                    && entry.path().extension().is_some_and(|ext| ext == "html")
+            })
+            .map(|entry| entry.into_path());
+
+        for path in bundles.chain(pages) {
+            let Ok(content) = fs::read(&path) else {
+                continue;
+            };
+            let content = String::from_utf8_lossy(&content);
+            if let Some((key, _)) = secrets.iter().find(|(_, value)| content.contains(*value)) {
+                return Err(anyhow!(
+                    "The value of `{key}` appears in the client output {}: only variables \
+                     prefixed with `{PUBLIC_PREFIX}` may reach the client",
+                    path.display()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the replacements of the public variables in the bundles, by expression
+    /// (e.g. `process.env.METASSR_PUBLIC_API_URL`).
+    pub fn defines(&self) -> BTreeMap<String, String> {
+        self.public()
+            .map(|(key, value)| (format!("process.env.{key}"), value.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::temp_dir;
+    use metassr_fs_analyzer::dist_dir::ClientEntry;
+
+    #[test]
+    fn parsing_env_files() {
+        let content = r#"
+# Comment
+export API_URL=https://api.example.com # trailing comment
+EMPTY=
+QUOTED="line\nbreak \"quoted\""
+LITERAL='no\nescape'
+SPACED = value
+"#;
+        assert_eq!(
+            parse(content, ".env").unwrap(),
+            vec![
+                ("API_URL".into(), "https://api.example.com".into()),
+                ("EMPTY".into(), "".into()),
+                ("QUOTED".into(), "line\nbreak \"quoted\"".into()),
+                ("LITERAL".into(), "no\\nescape".into()),
+                ("SPACED".into(), "value".into()),
+            ]
+        );
+
+        let err = parse("VALID=1\nnot a variable", ".env").unwrap_err();
+        assert!(err.to_string().contains(".env:2"));
+        assert!(parse("1KEY=value", ".env").is_err());
+        assert!(parse("KEY=\"unterminated", ".env").is_err());
+    }
+
+    #[test]
+    fn loading_env_files() {
+        let root = temp_dir();
+        fs::write(
+            root.join(".env"),
+            "METASSR_PUBLIC_TITLE=Base\nDATABASE_URL=postgres://base\n",
+        )
+        .unwrap();
+        fs::write(root.join(".env.production"), "METASSR_PUBLIC_TITLE=Prod\n").unwrap();
+        fs::write(root.join(".env.development"), "METASSR_PUBLIC_TITLE=Dev\n").unwrap();
+        fs::write(root.join(".env.local"), "DATABASE_URL=postgres://local\n").unwrap();
+
+        let env = Env::load(&root, BuildMode::Production).unwrap();
+        assert_eq!(env.vars()["METASSR_PUBLIC_TITLE"], "Prod");
+        assert_eq!(env.vars()["DATABASE_URL"], "postgres://local");
+        assert_eq!(
+            env.defines().get("process.env.METASSR_PUBLIC_TITLE"),
+            Some(&"Prod".to_owned())
+        );
+        assert!(!env.defines().contains_key("process.env.DATABASE_URL"));
+
+        let env = Env::load(&root, BuildMode::Development).unwrap();
+        assert_eq!(env.vars()["METASSR_PUBLIC_TITLE"], "Dev");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn secrets_in_client_output() {
+        let dist = temp_dir();
+        fs::create_dir_all(dist.join("pages")).unwrap();
+        let env = Env {
+            vars: BTreeMap::from([
+                (
+                    "METASSR_PUBLIC_API_URL".into(),
+                    "https://api.example.com".into(),
+                ),
+                ("API_TOKEN".into(), "s3cr3t-t0k3n".into()),
+                ("PORT".into(), "3000".into()),
+            ]),
+        };
+        let manifest = ClientManifest {
+            entries: BTreeMap::from([(
+                "pages/index.js".to_string(),
+                ClientEntry {
+                    files: vec!["pages/index.js.1a2b3c4d.js".into()],
+                    ..Default::default()
+                },
+            )]),
+        };
+
+        let bundle = dist.join("pages/index.js.1a2b3c4d.js");
+        fs::write(
+            &bundle,
+            r#"fetch("https://api.example.com", { port: 3000 })"#,
+        )
+        .unwrap();
+        assert!(env.check_client_output(&dist, &manifest).is_ok());
+
+        fs::write(&bundle, r#"fetch(url, { token: "s3cr3t-t0k3n" })"#).unwrap();
+        let err = env.check_client_output(&dist, &manifest).unwrap_err();
+        assert!(err.to_string().contains("API_TOKEN"));
+
+        fs::write(&bundle, "").unwrap();
+        fs::write(dist.join("pages/index.js.1a2b3c4d.js.map"), "s3cr3t-t0k3n").unwrap();
+        assert!(env.check_client_output(&dist, &manifest).is_err());
+        fs::remove_file(dist.join("pages/index.js.1a2b3c4d.js.map")).unwrap();
+
+        // Static pages are client output too, along with their hydration data
+        let page = dist.join("pages/blog/index.html");
+        fs::create_dir_all(page.parent().unwrap()).unwrap();
+        fs::write(
+            &page,
+            r#"<script id="__METASSR_DATA__">{"token":"s3cr3t-t0k3n"}</script>"#,
+        )
+        .unwrap();
+        let err = env.check_client_output(&dist, &manifest).unwrap_err();
+        assert!(err.to_string().contains("pages/blog/index.html"));
+
+        // The server side isn't
+        fs::remove_file(&page).unwrap();
+        fs::create_dir_all(dist.join("cache")).unwrap();
+        fs::write(dist.join("cache/document.html"), "s3cr3t-t0k3n").unwrap();
+        assert!(env.check_client_output(&dist, &manifest).is_ok());
+
+        fs::remove_dir_all(dist).unwrap();
+    }
+}
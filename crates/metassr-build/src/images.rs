@@ -0,0 +1,472 @@
+use crate::traits::Build;
+use anyhow::{anyhow, Result};
+use image::{
+    codecs::{avif::AvifEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage, ImageReader,
+};
+use metassr_bundler::default_public_path;
+use metassr_fs_analyzer::dist_dir::CONTENT_HASH_LEN;
+use metassr_utils::{cache_dir::CacheDir, hash::content_hash};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsStr,
+    fmt::Display,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+/// The module request of the `<Image>` component, resolved to the modules generated by
+/// [`ImageOptimizer`]. Each image is imported from its own module under it, by its path from
+/// the project root (e.g. `metassr/image/static/images/hero.jpg`).
+pub const IMAGE_MODULE: &str = "metassr/image";
+
+/// The name of the image manifest, in the root of the `dist/` directory.
+pub const IMAGE_MANIFEST: &str = "image-manifest.json";
+
+/// The extensions of the images optimized by [`ImageOptimizer`]. Vector and animated images
+/// are left as they are.
+const SOURCE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "webp"];
+
+/// The `<Image>` component, rendering the images imported from their modules.
+const IMAGE_COMPONENT: &str = include_str!("scripts/image.js.template");
+
+/// A format images are re-encoded to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    /// Smaller files, supported by recent browsers.
+    Avif,
+    /// Supported by every modern browser. Variants are encoded losslessly.
+    Webp,
+}
+
+impl ImageFormat {
+    /// Encodes `image` in the format.
+    fn encode(&self, image: &DynamicImage) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        // Both encoders take 8-bit images
+        let image = DynamicImage::ImageRgba8(image.to_rgba8());
+        match self {
+            Self::Avif => {
+                image.write_with_encoder(AvifEncoder::new_with_speed_quality(&mut buf, 6, 75))?
+            }
+            Self::Webp => image.write_with_encoder(WebPEncoder::new_lossless(&mut buf))?,
+        }
+        Ok(buf)
+    }
+}
+
+impl Display for ImageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Avif => "avif",
+            Self::Webp => "webp",
+        })
+    }
+}
+
+/// The widths and formats images are optimized into, from the `images` of `metassr.config.json`.
+///
+/// ```json
+/// {
+///     "images": {
+///         "widths": [640, 1080, 1920],
+///         "formats": ["avif", "webp"]
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ImageOptions {
+    /// The widths of the variants, in pixels. Images are never upscaled: widths above an
+    /// image's own width are left out.
+    pub widths: Vec<u32>,
+    /// The formats of the variants, in the order browsers should prefer them.
+    pub formats: Vec<ImageFormat>,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            widths: vec![640, 1080, 1920],
+            formats: vec![ImageFormat::Avif, ImageFormat::Webp],
+        }
+    }
+}
+
+/// A resized and re-encoded copy of a source image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageVariant {
+    /// The URL of the variant, under the public path of the `dist/` directory, e.g.
+    /// `/dist/images/hero-640.1a2b3c4d.webp`.
+    pub src: String,
+    pub width: u32,
+    pub format: ImageFormat,
+}
+
+/// A source image and its variants.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageEntry {
+    /// The width of the source image, in pixels.
+    pub width: u32,
+    /// The height of the source image, in pixels.
+    pub height: u32,
+    /// The variants of the image, by format then width.
+    pub variants: Vec<ImageVariant>,
+}
+
+/// `ImageManifest` maps each source image, by its path from the project root
+/// (e.g. `static/images/hero.jpg`), to its variants.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageManifest {
+    pub images: BTreeMap<String, ImageEntry>,
+}
+
+impl ImageManifest {
+    /// Reads the image manifest of a `dist/` directory, if images were optimized.
+    pub fn read<S: AsRef<Path> + ?Sized>(dist: &S) -> Result<Option<Self>> {
+        let path = dist.as_ref().join(IMAGE_MANIFEST);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    /// Writes the image manifest to the root of a `dist/` directory.
+    pub fn write<S: AsRef<Path> + ?Sized>(&self, dist: &S) -> Result<()> {
+        fs::write(
+            dist.as_ref().join(IMAGE_MANIFEST),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// What an image optimization produced.
+#[derive(Debug, Clone, Default)]
+pub struct ImageOutput {
+    pub manifest: ImageManifest,
+    /// The directory of the generated modules, resolved as [`IMAGE_MODULE`]: the `<Image>`
+    /// component in `index.js`, and the module of each image, by its path from the project
+    /// root (e.g. `static/images/hero.jpg.js`).
+    pub module: PathBuf,
+}
+
+/// `ImageOptimizer` resizes the images of a project into the configured widths, and re-encodes
+/// them into the configured formats. The images are the ones in `static/images` and in `src`.
+///
+/// The variants are emitted under `dist/images`, named after their content hash. They're cached
+/// in `dist/cache/images` by the content hash of their source, so only new and changed images
+/// are encoded again. Variants of removed images are removed from both.
+pub struct ImageOptimizer {
+    root: PathBuf,
+    dist_path: PathBuf,
+    options: ImageOptions,
+}
+
+impl ImageOptimizer {
+    pub fn new<S>(root: &S, dist_dir: &str) -> Result<Self>
+    where
+        S: AsRef<OsStr> + ?Sized,
+    {
+        let root = PathBuf::from(root);
+        let dist_path = root.join(dist_dir);
+        if !dist_path.exists() {
+            fs::create_dir_all(&dist_path)?;
+        }
+        Ok(Self {
+            root,
+            dist_path,
+            options: ImageOptions::default(),
+        })
+    }
+
+    /// Sets the widths and formats of the variants.
+    pub fn options(mut self, options: ImageOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the source images, sorted by path.
+    fn sources(&self) -> Vec<PathBuf> {
+        let mut sources: Vec<PathBuf> = ["static/images", "src"]
+            .iter()
+            .flat_map(|dir| WalkDir::new(self.root.join(dir)))
+            .filter_map(|e| e.ok())
+            .filter(|e| {
+                e.file_type().is_file()
+                    && e.path()
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+            })
+            .map(|e| e.into_path())
+            .collect();
+        sources.sort();
+        sources
+    }
+
+    /// The widths of the variants of an image `width` pixels wide: the configured ones it
+    /// can be downscaled to, or its own width if it's narrower than all of them.
+    fn widths(&self, width: u32) -> Vec<u32> {
+        let mut widths: Vec<u32> = self
+            .options
+            .widths
+            .iter()
+            .copied()
+            .filter(|&w| w > 0 && w <= width)
+            .collect();
+        if widths.is_empty() {
+            widths.push(width);
+        }
+        widths.sort_unstable();
+        widths.dedup();
+        widths
+    }
+
+    /// Optimizes the image at `path`, writing its variants to `images_dir`. The cached variants
+    /// it uses are added to `cached`.
+    fn optimize(
+        &self,
+        path: &Path,
+        cache: &mut CacheDir,
+        images_dir: &Path,
+        cached: &mut HashSet<PathBuf>,
+    ) -> Result<ImageEntry> {
+        let bytes = fs::read(path)?;
+        let hash = content_hash(&bytes);
+        let (width, height) = ImageReader::new(Cursor::new(&bytes))
+            .with_guessed_format()?
+            .into_dimensions()
+            .map_err(|e| anyhow!("Cannot read image {}: {e}", path.display()))?;
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+
+        // The source is only decoded if a variant isn't cached
+        let mut image = None;
+        let mut variants = vec![];
+        for &format in &self.options.formats {
+            for width in self.widths(width) {
+                let name = format!("images/{hash}/{width}.{format}");
+                let cache_file = cache.path().join(&name);
+                let encoded = match fs::read(&cache_file) {
+                    Ok(encoded) => encoded,
+                    Err(_) => {
+                        if image.is_none() {
+                            image = Some(image::load_from_memory(&bytes).map_err(|e| {
+                                anyhow!("Cannot decode image {}: {e}", path.display())
+                            })?);
+                        }
+                        let resized =
+                            image
+                                .as_ref()
+                                .unwrap()
+                                .resize(width, u32::MAX, FilterType::Lanczos3);
+                        let encoded = format.encode(&resized)?;
+                        cache.insert(&name, &encoded)?;
+                        encoded
+                    }
+                };
+                cached.insert(cache_file);
+
+                let filename = format!(
+                    "{stem}-{width}.{}.{format}",
+                    &content_hash(&encoded)[..CONTENT_HASH_LEN]
+                );
+                let output = images_dir.join(&filename);
+                if !output.exists() {
+                    fs::write(&output, &encoded)?;
+                }
+                variants.push(ImageVariant {
+                    src: format!("{}images/{filename}", default_public_path(&self.dist_path)),
+                    width,
+                    format,
+                });
+            }
+        }
+
+        Ok(ImageEntry {
+            width,
+            height,
+            variants,
+        })
+    }
+}
+
+impl Build for ImageOptimizer {
+    type Output = ImageOutput;
+    fn build(&self) -> Result<Self::Output> {
+        let mut cache = CacheDir::new(&self.dist_path.join("cache"))?;
+        let images_dir = self.dist_path.join("images");
+        fs::create_dir_all(&images_dir)?;
+
+        let mut manifest = ImageManifest::default();
+        let mut cached = HashSet::new();
+        let mut modules = HashSet::new();
+        for path in self.sources() {
+            let source = path
+                .strip_prefix(&self.root)
+                .unwrap_or(&path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let entry = self.optimize(&path, &mut cache, &images_dir, &mut cached)?;
+
+            // Each image has its own module, so bundles only hold the images they import
+            let mut image = serde_json::to_value(&entry)?;
+            image["src"] = format!("/{source}").into();
+            let module = format!(
+                "export default {};\n",
+                serde_json::to_string_pretty(&image)?
+            );
+            modules.insert(cache.insert(&format!("image/{source}.js"), module.as_bytes())?);
+            manifest.images.insert(source, entry);
+        }
+        manifest.write(&self.dist_path)?;
+        clean_stale_variants(&images_dir, &manifest)?;
+        clean_stale_files(&cache.path().join("images"), &cached)?;
+
+        modules.insert(cache.insert("image/index.js", IMAGE_COMPONENT.as_bytes())?);
+        clean_stale_files(&cache.path().join("image"), &modules)?;
+        let module = cache.path().join("image").canonicalize()?;
+
+        Ok(ImageOutput { manifest, module })
+    }
+}
+
+/// Removes the variants that no image of the manifest has anymore.
+fn clean_stale_variants(images_dir: &Path, manifest: &ImageManifest) -> Result<()> {
+    let variants: HashSet<&str> = manifest
+        .images
+        .values()
+        .flat_map(|image| &image.variants)
+        .filter_map(|variant| variant.src.rsplit('/').next())
+        .collect();
+
+    for entry in fs::read_dir(images_dir)? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|name| name.to_str());
+        if path.is_file() && !name.is_some_and(|name| variants.contains(name)) {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the files of `dir` that aren't in `kept`, such as the cached variants and the
+/// modules of removed or changed images, along with the directories they leave empty.
+fn clean_stale_files(dir: &Path, kept: &HashSet<PathBuf>) -> Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    // Directories come after their contents, so they're empty once their stale files are removed
+    for entry in WalkDir::new(dir).min_depth(1).contents_first(true) {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type().is_file() && !kept.contains(path) {
+            fs::remove_file(path)?;
+        } else if entry.file_type().is_dir() && fs::read_dir(path)?.next().is_none() {
+            fs::remove_dir(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::temp_dir;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn optimizing_images() {
+        let root = temp_dir();
+        fs::create_dir_all(root.join("static/images")).unwrap();
+        fs::create_dir_all(root.join("src/pages")).unwrap();
+        RgbImage::from_fn(800, 400, |x, y| Rgb([x as u8, y as u8, 128]))
+            .save(root.join("static/images/hero.png"))
+            .unwrap();
+        RgbImage::from_fn(32, 32, |_, _| Rgb([255, 0, 0]))
+            .save(root.join("src/logo.png"))
+            .unwrap();
+
+        // Variant URLs follow the output directory
+        let optimizer = ImageOptimizer::new(&root, "out")
+            .unwrap()
+            .options(ImageOptions {
+                widths: vec![320, 640, 1080],
+                formats: vec![ImageFormat::Webp],
+            });
+        let output = optimizer.build().unwrap();
+
+        // Images are never upscaled, and narrow ones keep their own width
+        let hero = &output.manifest.images["static/images/hero.png"];
+        assert_eq!((hero.width, hero.height), (800, 400));
+        assert_eq!(
+            hero.variants.iter().map(|v| v.width).collect::<Vec<_>>(),
+            vec![320, 640]
+        );
+        let logo = &output.manifest.images["src/logo.png"];
+        assert_eq!(logo.variants.len(), 1);
+        assert_eq!(logo.variants[0].width, 32);
+
+        for variant in &hero.variants {
+            let file = variant.src.strip_prefix("/out/").unwrap();
+            assert!(root.join("out").join(file).exists());
+            assert!(variant.src.ends_with(".webp"));
+        }
+        assert_eq!(
+            ImageManifest::read(&root.join("out")).unwrap(),
+            Some(output.manifest.clone())
+        );
+
+        // Each image has its own module, apart from the component
+        let module = |path: &str| fs::read_to_string(output.module.join(path)).unwrap();
+        let hero_module = module("static/images/hero.png.js");
+        assert!(hero_module.contains(&hero.variants[0].src));
+        assert!(hero_module.contains(r#""src": "/static/images/hero.png""#));
+        assert!(!hero_module.contains(&logo.variants[0].src));
+        assert!(module("src/logo.png.js").contains(&logo.variants[0].src));
+        assert!(!module("index.js").contains(&hero.variants[0].src));
+
+        // Cached variants are reused as they are
+        let file = root
+            .join("out/images")
+            .join(hero.variants[0].src.rsplit('/').next().unwrap());
+        let modified = fs::metadata(&file).unwrap().modified().unwrap();
+        assert_eq!(optimizer.build().unwrap().manifest, output.manifest);
+        assert_eq!(fs::metadata(&file).unwrap().modified().unwrap(), modified);
+
+        // Variants of removed images are removed too, cached ones included
+        let logo_hash = content_hash(&fs::read(root.join("src/logo.png")).unwrap());
+        let hero_hash = content_hash(&fs::read(root.join("static/images/hero.png")).unwrap());
+        assert!(root.join("out/cache/images").join(&logo_hash).exists());
+        fs::remove_file(root.join("src/logo.png")).unwrap();
+        optimizer.build().unwrap();
+        let logo_file = logo.variants[0].src.rsplit('/').next().unwrap();
+        assert!(!root.join("out/images").join(logo_file).exists());
+        assert!(!root.join("out/cache/images").join(&logo_hash).exists());
+        assert!(root.join("out/cache/images").join(&hero_hash).exists());
+        assert!(!output.module.join("src").exists());
+        assert!(output.module.join("static/images/hero.png.js").exists());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn widths_of_variants() {
+        let optimizer = ImageOptimizer::new(&std::env::temp_dir(), "")
+            .unwrap()
+            .options(ImageOptions {
+                widths: vec![1080, 640, 640, 0],
+                formats: vec![ImageFormat::Avif],
+            });
+        assert_eq!(optimizer.widths(2000), vec![640, 1080]);
+        assert_eq!(optimizer.widths(800), vec![640]);
+        assert_eq!(optimizer.widths(100), vec![100]);
+    }
+}
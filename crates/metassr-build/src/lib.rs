@@ -1,4 +1,9 @@
+pub mod build_graph;
 pub mod client;
+pub mod config;
+pub mod env;
+pub mod images;
+pub mod project;
 pub mod server;
 pub(crate) mod shared;
 pub mod traits;
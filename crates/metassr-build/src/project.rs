@@ -0,0 +1,476 @@
+use crate::{
+    client::ClientBuilder,
+    env::Env,
+    images::IMAGE_MODULE,
+    server::{BuildingType, ServerBuild, ServerSideBuilder, SERVER},
+    traits::Build,
+};
+use anyhow::Result;
+use metassr_bundler::{
+    backends::Bundler,
+    options::{BundleOptions, Targets},
+    BundleOutput, MultiBundler,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// The name of the client compilation, bundled along with the server ones.
+const CLIENT: &str = "client";
+
+/// What a project build produced.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectOutput {
+    /// The bundled files and modules of each page.
+    pub client: BundleOutput,
+    /// The bundled files and modules of each page's render script.
+    pub server: BundleOutput,
+}
+
+/// `ProjectBuilder` builds both sides of a project: the hydration scripts of the client
+/// side, and the render scripts, head and document of the server side.
+///
+/// Every bundle is built in a single bundler run, so modules shared by both sides (such as
+/// the pages and their dependencies) are resolved once.
+pub struct ProjectBuilder {
+    client: ClientBuilder,
+    server: ServerSideBuilder,
+    image_module: Option<PathBuf>,
+    env: Option<Env>,
+    root: PathBuf,
+    dist_path: PathBuf,
+}
+
+impl ProjectBuilder {
+    pub fn new<S>(root: &S, dist_dir: &str, building_type: BuildingType) -> Result<Self>
+    where
+        S: AsRef<OsStr> + ?Sized,
+    {
+        Ok(Self {
+            client: ClientBuilder::new(root, dist_dir)?,
+            server: ServerSideBuilder::new(root, dist_dir, building_type)?,
+            image_module: None,
+            env: None,
+            root: PathBuf::from(root),
+            dist_path: Path::new(root).join(dist_dir),
+        })
+    }
+
+    /// Limits the build to `pages`, given as routes of the source directory pages
+    /// (e.g. `blog/index.tsx`). All pages are built by default.
+    pub fn pages(mut self, pages: HashSet<String>) -> Self {
+        self.client = self.client.pages(pages.clone());
+        self.server = self.server.pages(pages);
+        self
+    }
+
+    /// Sets the build mode and source maps. Production ones are used by default.
+    pub fn options(mut self, options: BundleOptions) -> Self {
+        self.client = self.client.options(options);
+        self.server = self.server.options(options);
+        self
+    }
+
+    /// Builds both sides with `backend` instead of the backend selected by the options, e.g. a
+    /// bundler provided by an embedder. Every bundle is still built in a single run.
+    pub fn backend(mut self, backend: Arc<dyn Bundler>) -> Self {
+        self.client = self.client.backend(Arc::clone(&backend));
+        self.server = self.server.backend(backend);
+        self
+    }
+
+    /// Mixes `revision` into the IDs of the render functions (see
+    /// [`ServerSideBuilder::revision`]).
+    pub fn revision(mut self, revision: u64) -> Self {
+        self.server = self.server.revision(revision);
+        self
+    }
+
+    /// Sets the number of workers rendering the static pages of an SSG build in parallel (see
+    /// [`ServerSideBuilder::ssg_workers`]).
+    pub fn ssg_workers(mut self, workers: usize) -> Self {
+        self.server = self.server.ssg_workers(workers);
+        self
+    }
+
+    /// Sets how long a static page of an SSG build can take to render before it fails (see
+    /// [`ServerSideBuilder::ssg_timeout`]).
+    pub fn ssg_timeout(mut self, timeout: Duration) -> Self {
+        self.server = self.server.ssg_timeout(timeout);
+        self
+    }
+
+    /// Sets the browsers the client side is built for. The server side targets the Node version
+    /// running MetaSSR.
+    pub fn browser_targets(mut self, targets: Targets) -> Self {
+        self.client = self.client.browser_targets(targets);
+        self
+    }
+
+    /// Resolves imports of the `<Image>` component (`metassr/image`) to `module`, generated
+    /// by [`ImageOptimizer`](crate::images::ImageOptimizer).
+    pub fn image_module<P: AsRef<Path> + ?Sized>(mut self, module: &P) -> Self {
+        self.image_module = Some(module.as_ref().to_path_buf());
+        self
+    }
+
+    /// Inlines the public variables of `env` into the bundles, and verifies that none of its
+    /// secrets reached the client ones.
+    pub fn env(mut self, env: Env) -> Self {
+        self.env = Some(env);
+        self
+    }
+}
+
+impl ProjectBuilder {
+    /// Builds the project like [`Build::build`], awaiting the bundling instead of blocking on
+    /// it, e.g. from an async runtime.
+    pub async fn bundle(&self) -> Result<ProjectOutput> {
+        let targets = self.client.targets()?;
+        let server = self.server.prepare()?;
+        let outputs = self.bundler(&targets, &server)?.bundle().await?;
+        self.finish(outputs, server)
+    }
+
+    /// The bundler of every bundle of both sides, from the targets of the client side and the
+    /// prepared server side.
+    fn bundler<'a>(
+        &'a self,
+        targets: &'a HashMap<String, String>,
+        server: &'a ServerBuild,
+    ) -> Result<MultiBundler<'a>> {
+        let bundler = [(CLIENT, self.client.bundler(targets)?)]
+            .into_iter()
+            .chain(self.server.bundlers(server)?)
+            .fold(MultiBundler::new(), |bundler, (name, compilation)| {
+                // The bundles are the same wherever the project is
+                let compilation = compilation.context(&self.root);
+                let compilation = match &self.image_module {
+                    Some(module) => compilation.alias(IMAGE_MODULE, module),
+                    None => compilation,
+                };
+                let compilation = self
+                    .env
+                    .iter()
+                    .flat_map(Env::defines)
+                    .fold(compilation, |compilation, (expression, value)| {
+                        compilation.define(expression, &value)
+                    });
+                bundler.add(name, compilation)
+            });
+        Ok(bundler)
+    }
+
+    /// Completes both sides with what their bundling produced.
+    fn finish(
+        &self,
+        mut outputs: HashMap<String, BundleOutput>,
+        server: ServerBuild,
+    ) -> Result<ProjectOutput> {
+        // The server side needs the client manifest to link each page to its scripts.
+        let client = outputs.remove(CLIENT).unwrap_or_default();
+        let manifest = self.client.finish(&client)?;
+        self.server.finish(server)?;
+        // Checked once the static pages are generated, as they're client output too
+        if let Some(env) = &self.env {
+            env.check_client_output(&self.dist_path, &manifest)?;
+        }
+
+        Ok(ProjectOutput {
+            client,
+            server: outputs.remove(SERVER).unwrap_or_default(),
+        })
+    }
+}
+
+impl Build for ProjectBuilder {
+    type Output = ProjectOutput;
+    fn build(&self) -> Result<Self::Output> {
+        let targets = self.client.targets()?;
+        let server = self.server.prepare()?;
+
+        // Bundling errors of both sides are returned as is, so their diagnostics reach the caller.
+        let outputs = self.bundler(&targets, &server)?.exec()?;
+        self.finish(outputs, server)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{manifest::Manifest, renderer::page::PageRenderer};
+    use metassr_bundler::{backends::BundleFuture, Compilation};
+    use std::{
+        collections::{BTreeMap, HashMap},
+        fs,
+        sync::Mutex,
+    };
+    use walkdir::WalkDir;
+
+    /// Reads every file of `dir`, by path.
+    fn snapshot(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        WalkDir::new(dir)
+            .into_iter()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| (entry.path().to_path_buf(), fs::read(entry.path()).unwrap()))
+            .collect()
+    }
+
+    /// Copies the sources of the `web-app` project into `web-app/<name>`, so the copy can be
+    /// changed, while still using the dependencies of `web-app`.
+    fn copy_project(name: &str) -> PathBuf {
+        let root = Path::new("../../tests/web-app");
+        let copy = root.join(name);
+        if copy.exists() {
+            fs::remove_dir_all(&copy).unwrap();
+        }
+        for dir in ["src", "static"] {
+            for entry in WalkDir::new(root.join(dir)) {
+                let entry = entry.unwrap();
+                let target = copy.join(entry.path().strip_prefix(root).unwrap());
+                if entry.file_type().is_dir() {
+                    fs::create_dir_all(target).unwrap();
+                } else {
+                    fs::copy(entry.path(), target).unwrap();
+                }
+            }
+        }
+        for file in ["package.json", "tsconfig.json"] {
+            fs::copy(root.join(file), copy.join(file)).unwrap();
+        }
+        copy
+    }
+
+    #[test]
+    fn rebuilt_pages_render_their_changes() {
+        let _metacall = metacall::switch::initialize().unwrap();
+        let root = copy_project("rebuilt-project");
+        let dist = root.join("dist");
+        let build = |revision| {
+            ProjectBuilder::new(&root, "dist", BuildingType::ServerSideRendering)
+                .unwrap()
+                .options(BundleOptions::default().cache(false))
+                .revision(revision)
+                .build()
+                .unwrap();
+            PageRenderer::from_manifest(&dist, "#root")
+                .unwrap()
+                .render()
+                .unwrap()
+        };
+
+        let html = build(0);
+        assert!(html.contains("Hello from index page"));
+        assert!(html.contains("<title>My website</title>"));
+
+        // The page and the head are rebuilt at the same paths, like `metassr dev` does
+        let page = root.join("src/pages/index.tsx");
+        let source = fs::read_to_string(&page).unwrap();
+        fs::write(&page, source.replace("Hello from", "Hello again from")).unwrap();
+        let head = root.join("src/_head.tsx");
+        let source = fs::read_to_string(&head).unwrap();
+        fs::write(&head, source.replace("My website", "My rebuilt website")).unwrap();
+
+        let html = build(1);
+        assert!(html.contains("Hello again from index page"));
+        assert!(html.contains("<title>My rebuilt website</title>"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn rebuilt_documents_render_their_changes() {
+        let _metacall = metacall::switch::initialize().unwrap();
+        let root = copy_project("rebuilt-document");
+        let dist = root.join("dist");
+        let document = root.join("src/_document.tsx");
+        let write_document = |class: &str| {
+            let source = format!(
+                r#"import React from 'react';
+
+export default function Document() {{
+    return (
+        <html lang="%LANG%" className="{class}">
+            <head>%HEAD% %STYLES%</head>
+            <body>%BODY% %SCRIPTS%</body>
+        </html>
+    );
+}}
+"#
+            );
+            fs::write(&document, source).unwrap();
+        };
+        let build = |revision| {
+            ProjectBuilder::new(&root, "dist", BuildingType::ServerSideRendering)
+                .unwrap()
+                .options(BundleOptions::default().cache(false))
+                .revision(revision)
+                .build()
+                .unwrap();
+            PageRenderer::from_manifest(&dist, "#root")
+                .unwrap()
+                .render()
+                .unwrap()
+        };
+
+        write_document("first");
+        assert!(build(0).contains(r#"class="first""#));
+
+        // The document is rebuilt at the same path, like `metassr dev` does
+        write_document("second");
+        let html = build(1);
+        assert!(html.contains(r#"class="second""#));
+        assert!(!html.contains(r#"class="first""#));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn builds_are_reproducible() {
+        let _metacall = metacall::switch::initialize().unwrap();
+        let root = Path::new("../../tests/web-app");
+        let dist = root.join("dist-reproducible");
+        let build = || {
+            if dist.exists() {
+                fs::remove_dir_all(&dist).unwrap();
+            }
+            ProjectBuilder::new(root, "dist-reproducible", BuildingType::ServerSideRendering)
+                .unwrap()
+                .options(BundleOptions::default().cache(false))
+                .build()
+                .unwrap();
+            snapshot(&dist)
+        };
+
+        let first = build();
+        let second = build();
+        assert!(first.keys().any(|file| file.ends_with("manifest.json")));
+        assert_eq!(
+            first.keys().collect::<Vec<_>>(),
+            second.keys().collect::<Vec<_>>()
+        );
+        for (file, content) in &first {
+            assert!(second[file] == *content, "{} differs", file.display());
+        }
+        fs::remove_dir_all(&dist).unwrap();
+    }
+
+    #[test]
+    fn builds_are_reproducible_across_directories() {
+        let _metacall = metacall::switch::initialize().unwrap();
+        let build = |name: &str| {
+            let root = copy_project(name);
+            let dist = root.join("dist");
+            ProjectBuilder::new(&root, "dist", BuildingType::StaticSiteGeneration)
+                .unwrap()
+                .options(BundleOptions::default().cache(false))
+                .build()
+                .unwrap();
+
+            // The manifest records where the server files are, in the cache: only the IDs of
+            // the render functions are compared
+            let manifest = Manifest::from(&dist);
+            let ids: Vec<i64> = ["#root", "home", "blog"]
+                .iter()
+                .map(|route| manifest.get(route).unwrap().id)
+                .collect();
+            let output: BTreeMap<PathBuf, Vec<u8>> = snapshot(&dist)
+                .into_iter()
+                .map(|(file, content)| (file.strip_prefix(&dist).unwrap().to_path_buf(), content))
+                .filter(|(file, _)| {
+                    !file.starts_with("cache") && file != Path::new("manifest.json")
+                })
+                .collect();
+            fs::remove_dir_all(&root).unwrap();
+            (ids, output)
+        };
+
+        let (first_ids, first) = build("reproducible-first");
+        let (second_ids, second) = build("reproducible-second");
+        assert_eq!(first_ids, second_ids);
+        assert!(first.keys().any(|file| file.ends_with("index.html")));
+        assert_eq!(
+            first.keys().collect::<Vec<_>>(),
+            second.keys().collect::<Vec<_>>()
+        );
+        for (file, content) in &first {
+            assert!(second[file] == *content, "{} differs", file.display());
+        }
+    }
+
+    #[test]
+    fn static_pages_merge_heads() {
+        let _metacall = metacall::switch::initialize().unwrap();
+        let root = Path::new("../../tests/web-app");
+        let dist = root.join("dist-heads");
+        if dist.exists() {
+            fs::remove_dir_all(&dist).unwrap();
+        }
+        ProjectBuilder::new(root, "dist-heads", BuildingType::StaticSiteGeneration)
+            .unwrap()
+            .options(BundleOptions::default().cache(false))
+            .build()
+            .unwrap();
+
+        let html = fs::read_to_string(dist.join("pages/blog/index.html")).unwrap();
+        let count = |pattern: &str| html.matches(pattern).count();
+
+        // The page overrides its layout, which overrides the global head
+        assert_eq!(count("<title>"), 1);
+        assert!(html.contains("<title>Blog</title>"));
+        assert_eq!(count(r#"name="author""#), 1);
+        assert!(html.contains(r#"<meta name="author" content="MetaSSR"/>"#));
+        assert_eq!(count(r#"name="description""#), 1);
+        assert!(html.contains(r#"content="A cool blog built with MetaSSR""#));
+
+        // Components are rendered, even the ones using hooks, and deduplicated like the tags
+        assert_eq!(count(r#"name="keywords""#), 1);
+        assert!(html.contains(r#"<meta name="keywords" content="blog, metassr"/>"#));
+        assert_eq!(count(r#"name="generator""#), 1);
+        assert!(html.contains(r#"<meta name="generator" content="MetaSSR Blog"/>"#));
+
+        // Tags with the same key replace each other
+        assert_eq!(count(r#"rel="alternate""#), 1);
+        assert!(html.contains("/blog/rss.xml"));
+
+        // The global tags that aren't overridden are kept
+        assert!(html.contains(r#"<meta charSet="UTF-8"/>"#));
+        fs::remove_dir_all(&dist).unwrap();
+    }
+
+    /// A bundler recording the compilations of each of its runs, without emitting anything.
+    #[derive(Debug, Default)]
+    struct Recorder(Mutex<Vec<Vec<String>>>);
+
+    impl Bundler for Recorder {
+        fn bundle(&self, compilations: &[Compilation]) -> Result<BundleFuture> {
+            let mut names: Vec<String> = compilations.iter().map(|c| c.name.to_owned()).collect();
+            names.sort();
+            self.0.lock().unwrap().push(names);
+            Ok(Box::pin(async { Ok(HashMap::new()) }))
+        }
+    }
+
+    #[test]
+    fn building_with_a_custom_backend() {
+        let _metacall = metacall::switch::initialize().unwrap();
+        let root = Path::new("../../tests/web-app");
+        let dist = root.join("dist-backend");
+        let recorder = Arc::new(Recorder::default());
+
+        // Nothing is emitted, so only the bundling run matters
+        let _ = ProjectBuilder::new(root, "dist-backend", BuildingType::ServerSideRendering)
+            .unwrap()
+            .backend(recorder.clone())
+            .build();
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![vec!["client", "head", "server"]]
+        );
+        fs::remove_dir_all(&dist).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+// Collects the tags of head trees, shared by the head script (`_head`) and the render scripts
+// (the `head` export of the pages and layouts). Written next to them in the cache directory.
+import React from "react"
+import { renderToStaticMarkup } from "react-dom/server"
+
+const MEMO = Symbol.for("react.memo");
+const FORWARD_REF = Symbol.for("react.forward_ref");
+const PROVIDER = Symbol.for("react.provider");
+const CONTEXT = Symbol.for("react.context");
+const CONSUMER = Symbol.for("react.consumer");
+
+/**
+ * Collects the intrinsic elements (title, meta, link, ...) of a head tree as plain tags, which
+ * can be sent as JSON. Components are walked through the elements they render.
+ * @param {*} node - The head tree.
+ * @param {Array<Object>} tags - The tags collected so far.
+ * @returns {Array<Object>} - The tags: their `type`, `key`, `props` and text `children`, or
+ * their `html` when their children aren't text.
+ */
+export function headToTags(node, tags = []) {
+    if (Array.isArray(node)) {
+        node.forEach((child) => headToTags(child, tags));
+    } else if (React.isValidElement(node)) {
+        const { type, props } = node;
+        if (typeof type === "string") {
+            const { children, ...attributes } = props;
+            const text = [].concat(children ?? []);
+            const tag = { type, key: node.key, props: attributes };
+            if (text.every((child) => typeof child === "string" || typeof child === "number")) {
+                tags.push({ ...tag, children: text.join("") || undefined });
+            } else {
+                tags.push({ ...tag, html: renderToStaticMarkup(node) });
+            }
+        } else if (typeof type === "symbol" || type.$$typeof === PROVIDER
+            || (type.$$typeof === CONTEXT && !type._context)) {
+            // Fragments, `StrictMode`, `Suspense` and context providers are their children
+            headToTags(props.children, tags);
+        } else {
+            headToTags(renderComponent(type, props), tags);
+        }
+    }
+    return tags;
+}
+
+/**
+ * Returns what a component renders. It's called while React renders a placeholder, so the
+ * component can use hooks as in any render, its contexts having their default values.
+ * @param {*} type - The component.
+ * @param {Object} props - The props of the component.
+ * @returns {*} - The rendered elements.
+ */
+function renderComponent(type, props) {
+    let rendered = null;
+    function Placeholder() {
+        rendered = callComponent(type, props);
+        return null;
+    }
+    renderToStaticMarkup(React.createElement(Placeholder));
+    return rendered;
+}
+
+/**
+ * Calls a component of any kind: a function or class component, or one wrapped by `memo`,
+ * `forwardRef` or a context consumer.
+ * @param {*} type - The component.
+ * @param {Object} props - The props of the component.
+ * @returns {*} - The rendered elements.
+ */
+function callComponent(type, props) {
+    if (type.prototype?.isReactComponent) {
+        const instance = new type(props);
+        instance.props = props;
+        return instance.render();
+    }
+    if (typeof type === "function") {
+        return type(props);
+    }
+    switch (type.$$typeof) {
+        case MEMO:
+            return callComponent(type.type, props);
+        case FORWARD_REF:
+            return type.render(props, null);
+        case CONTEXT: // The consumers of React 18
+        case CONSUMER:
+            return props.children(React.useContext(type._context));
+        default:
+            throw new Error(`Head elements can't be rendered by ${String(type.$$typeof ?? type)} components`);
+    }
+}
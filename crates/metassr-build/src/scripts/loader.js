@@ -0,0 +1,41 @@
+// Loads the server scripts (the render scripts, the head and the document) and calls their
+// functions. Node caches modules by path, and a rebuilt script keeps its path, so each script
+// is loaded under a key changing with its builds, and loaded again from its file, out of the
+// module cache, when the key changes.
+process.setSourceMapsEnabled?.(true);
+
+// The loaded scripts by path: their key and their exports.
+const scripts = new Map();
+
+/**
+ * Returns the exports of a script, loaded again if it was rebuilt under a new key.
+ * @param {string} file - The absolute path of the script.
+ * @param {string} key - The key of the build of the script.
+ * @returns {Object} - The exports of the script.
+ */
+function load(file, key) {
+    const script = scripts.get(file);
+    if (script?.key === key) {
+        return script.exports;
+    }
+    delete require.cache[file];
+    const exports = require(file);
+    scripts.set(file, { key, exports });
+    return exports;
+}
+
+/**
+ * Calls a function of a script, loading the script first if needed.
+ * @param {string} file - The absolute path of the script.
+ * @param {string} key - The key of the build of the script.
+ * @param {string} name - The name of the function.
+ * @param {string} args - The arguments of the function as a JSON array.
+ * @returns {string} - What the function returned.
+ */
+function call_script(file, key, name, args) {
+    return load(file, key)[name](...JSON.parse(args));
+}
+
+module.exports = {
+    call_script,
+};
@@ -0,0 +1,215 @@
+const { Worker, MessageChannel, receiveMessageOnPort } = require('worker_threads');
+
+// The code of each worker: it loads the render scripts it's sent pages of, renders the pages,
+// and reports each one on its port, counting it in the shared `done` counter.
+const WORKER = `
+const { workerData } = require('worker_threads');
+process.setSourceMapsEnabled?.(true);
+
+const { port, signal } = workerData;
+const done = new Int32Array(signal);
+const scripts = new Map();
+
+// Reports a message on the port, waking the pool up.
+function report(message) {
+    port.postMessage(message);
+    Atomics.add(done, 0, 1);
+    Atomics.notify(done, 0);
+}
+
+// A page can kill the worker, by throwing asynchronously or exiting the process. The worker
+// reports its death with the error killing it, so the pool fails the page it was rendering
+// instead of waiting for it.
+let fatal = null;
+process.on('uncaughtExceptionMonitor', (error) => {
+    fatal = error;
+});
+process.on('exit', (code) => {
+    report({
+        exit: fatal
+            ? { message: String(fatal?.message ?? fatal), stack: String(fatal?.stack ?? '') }
+            : { message: 'The page exited the process with code ' + code, stack: '' },
+    });
+});
+
+// Returns the render script at \`file\`, loaded again if it was rebuilt under a new ID.
+function load(file, id) {
+    const key = file + '#' + id;
+    if (!scripts.has(key)) {
+        delete require.cache[file];
+        scripts.set(key, require(file));
+    }
+    return scripts.get(key);
+}
+
+port.on('message', ({ index, file, id, props, params }) => {
+    let result;
+    try {
+        const script = load(file, id);
+        result = {
+            index,
+            render: script['render_' + id](props),
+            head: script['page_head_' + id](props, params),
+        };
+    } catch (error) {
+        result = { index, error: { message: String(error?.message ?? error), stack: String(error?.stack ?? '') } };
+    }
+    // Reported on the next turn of the event loop, so the errors a page throws asynchronously
+    // right away (rejected promises, immediate timers) kill the worker while it still renders
+    // the page, and fail the page rather than the next one.
+    setTimeout(() => report(result));
+});
+`;
+
+// How long to wait for a page before checking the ports again, in milliseconds. The pool can't
+// receive the events of the workers while it waits, so it also checks the deadlines of the
+// pages then.
+const POLL_INTERVAL = 1000;
+
+// The workers of the pool, created on the first call.
+let pool = null;
+
+/**
+ * Starts a worker, an isolated runtime with its own module cache, in a slot of the pool.
+ * @param {SharedArrayBuffer} signal - The counter of the messages reported by the workers.
+ * @param {Object} slot - The slot, a new one by default.
+ * @returns {Object} - The slot: the worker, its port, and the index of the page it renders, with the time it was sent.
+ */
+function spawn(signal, slot = {}) {
+    const { port1, port2 } = new MessageChannel();
+    const worker = new Worker(WORKER, { eval: true, workerData: { port: port2, signal }, transferList: [port2] });
+    worker.unref(); // The pool doesn't keep the process alive
+    // The worker reports its errors itself, along with its death: an unhandled `error` event
+    // would crash the process running the pool.
+    worker.on('error', () => {});
+    worker.on('exit', () => {
+        if (slot.worker === worker) {
+            slot.exited = true;
+        }
+    });
+    return Object.assign(slot, { worker, port: port1, job: null, started: 0, exited: false });
+}
+
+/**
+ * Replaces the worker of a slot that died with a new one.
+ * @param {Object} slot - The slot of the dead worker.
+ * @param {SharedArrayBuffer} signal - The counter of the messages reported by the workers.
+ */
+function respawn(slot, signal) {
+    slot.port.close();
+    slot.worker.terminate();
+    spawn(signal, slot);
+}
+
+/**
+ * Creates a pool of workers.
+ * @param {number} size - The number of workers.
+ * @returns {Object} - The slots of the workers, and the counter of the messages they reported.
+ */
+function createPool(size) {
+    const signal = new SharedArrayBuffer(4);
+    const workers = Array.from({ length: size }, () => spawn(signal));
+    return { workers, signal, done: new Int32Array(signal) };
+}
+
+/**
+ * Renders pages across the pool, each worker getting the next page once it's done with its
+ * own. It blocks until every page is rendered, reading the results from the workers' ports
+ * synchronously, so it can be called through MetaCall like the render functions.
+ *
+ * A worker killed by a page (an error thrown asynchronously, a call to `process.exit`) fails
+ * the page it was rendering, and is replaced by a new one for the next pages. So does a worker
+ * still rendering its page after `timeout`: a page that never returns, or a worker that died
+ * without reporting it.
+ * @param {string} batch - The batch as JSON: the number of `workers`, the `timeout` of each page in milliseconds, and the `jobs`, an array of `{ file, id, props, params }` (a render script, the ID of its functions, and their arguments).
+ * @returns {string} - The result of each page as a JSON array, in the order of the jobs: the guarded results of its `render` and `head` functions, or the `error` that prevented calling them.
+ */
+function render_pages(batch) {
+    const { workers: size, timeout, jobs } = JSON.parse(batch);
+    if (pool?.workers.length !== size) {
+        stop_render_pool();
+        pool = createPool(size);
+    }
+
+    // Workers that died since the last batch (e.g. of an error a page threw late) are replaced
+    for (const slot of pool.workers) {
+        let message;
+        while ((message = receiveMessageOnPort(slot.port))) {
+            slot.exited ||= Boolean(message.message.exit);
+        }
+        if (slot.exited) {
+            respawn(slot, pool.signal);
+        }
+    }
+
+    const results = new Array(jobs.length);
+    let next = 0;
+    let received = 0;
+    const fail = (slot, message, stack = '') => {
+        if (slot.job !== null) {
+            results[slot.job] = { index: slot.job, error: { message, stack } };
+            received++;
+        }
+        respawn(slot, pool.signal);
+    };
+    const send = (slot) => {
+        slot.job = null;
+        if (next < jobs.length) {
+            slot.job = next;
+            slot.started = Date.now();
+            slot.port.postMessage({ index: next, ...jobs[next] });
+            next++;
+        }
+    };
+    pool.workers.forEach(send);
+
+    while (received < jobs.length) {
+        // Read before polling: pages reported meanwhile change it, so the wait returns at once
+        const seen = Atomics.load(pool.done, 0);
+        let reported = false;
+        for (const slot of pool.workers) {
+            let message;
+            while ((message = receiveMessageOnPort(slot.port))) {
+                const { exit } = message.message;
+                if (exit) {
+                    // The worker died: the page it was rendering failed, the next go to a new one
+                    fail(slot, `The render worker stopped: ${exit.message}`, exit.stack);
+                } else {
+                    results[message.message.index] = message.message;
+                    received++;
+                }
+                reported = true;
+                send(slot);
+            }
+        }
+        // Workers past the deadline of their page are stopped, whatever they're doing
+        const now = Date.now();
+        for (const slot of pool.workers) {
+            if (slot.job !== null && now - slot.started >= timeout) {
+                fail(slot, `The page didn't render within ${timeout}ms`);
+                reported = true;
+                send(slot);
+            }
+        }
+        if (!reported) {
+            Atomics.wait(pool.done, 0, seen, Math.min(POLL_INTERVAL, timeout));
+        }
+    }
+    return JSON.stringify(results);
+}
+
+/**
+ * Terminates the workers of the pool, if there are any.
+ */
+function stop_render_pool() {
+    for (const { worker, port } of pool?.workers ?? []) {
+        port.close();
+        worker.terminate();
+    }
+    pool = null;
+}
+
+module.exports = {
+    render_pages,
+    stop_render_pool,
+};
@@ -0,0 +1,55 @@
+use std::{path::Path, sync::Mutex, time::UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use metacall::{loaders, metacall};
+use metassr_utils::checker::CheckerState;
+
+const LOADER_SCRIPT: &str = include_str!("../scripts/loader.js");
+
+lazy_static! {
+    static ref IS_LOADER_LOADED: Mutex<CheckerState> = Mutex::new(CheckerState::default());
+}
+
+/// Calls the function `name` of the server script at `path` (a render script, the head or the
+/// document) with `args`, and returns what it returned.
+///
+/// Node caches the scripts by path, and a rebuilt script keeps its path, so the script is
+/// loaded again whenever `key` changes: the ID of a render script, which changes with the
+/// revision, or the modification time of the other scripts (see [`modified`]).
+pub(crate) fn call<const N: usize>(
+    path: &Path,
+    key: &str,
+    name: &str,
+    args: [String; N],
+) -> Result<String> {
+    let mut guard = IS_LOADER_LOADED.lock().unwrap();
+    if !guard.is_true() {
+        if let Err(e) = loaders::from_memory("node", LOADER_SCRIPT) {
+            return Err(anyhow!("Cannot load the script loader: {e:?}"));
+        }
+        guard.make_true();
+    }
+    drop(guard);
+
+    let path = match path.canonicalize() {
+        Ok(path) => path,
+        Err(e) => return Err(anyhow!("Cannot load script {path:#?}: {e}")),
+    };
+    let args = [
+        path.to_str().unwrap().to_string(),
+        key.to_string(),
+        name.to_string(),
+        serde_json::to_string(args.as_slice())?,
+    ];
+    match metacall::<String>("call_script", args) {
+        Ok(out) => Ok(out),
+        Err(e) => Err(anyhow!("Cannot run {name}() of {path:#?}: {e:?}")),
+    }
+}
+
+/// The modification time of `path`, the key of the scripts rebuilt without a new ID.
+pub(crate) fn modified(path: &Path) -> Result<String> {
+    let time = path.metadata()?.modified()?.duration_since(UNIX_EPOCH)?;
+    Ok(time.as_nanos().to_string())
+}
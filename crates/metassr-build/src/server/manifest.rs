@@ -6,7 +6,7 @@ use metassr_utils::cache_dir::CacheDir;
 use serde::{Deserialize, Serialize};
 use serde_json::to_string_pretty;
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     ffi::OsStr,
     fs::{read_to_string, File},
     io::Write,
@@ -36,10 +36,13 @@ impl ManifestEntry {
 pub struct GlobalEntry {
     pub head: PathBuf,
     pub cache: PathBuf,
+    /// The rendered custom document, if the project has a `_document` file.
+    #[serde(default)]
+    pub document: Option<PathBuf>,
 }
 
 impl GlobalEntry {
-    pub fn new<H, C>(head: &H, cache: &C) -> Result<Self>
+    pub fn new<H, C>(head: &H, cache: &C, document: Option<PathBuf>) -> Result<Self>
     where
         H: AsRef<OsStr> + ?Sized,
         C: AsRef<OsStr> + ?Sized,
@@ -47,6 +50,10 @@ impl GlobalEntry {
         Ok(Self {
             head: PathBuf::from(head).canonicalize()?,
             cache: PathBuf::from(cache),
+            document: match document {
+                Some(path) => Some(path.canonicalize()?),
+                None => None,
+            },
         })
     }
 }
@@ -54,14 +61,15 @@ impl GlobalEntry {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Manifest {
     pub global: GlobalEntry,
-    routes: HashMap<String, ManifestEntry>,
+    /// Ordered by route, so the same build always writes the same manifest.
+    routes: BTreeMap<String, ManifestEntry>,
 }
 
 impl Manifest {
     pub fn new(global: GlobalEntry) -> Self {
         Self {
             global,
-            routes: HashMap::new(),
+            routes: BTreeMap::new(),
         }
     }
 
@@ -92,6 +100,12 @@ impl Manifest {
     pub fn get(&self, route: &str) -> Option<&ManifestEntry> {
         self.routes.get(route)
     }
+
+    /// Merges `other` into the manifest: its global entry and routes replace the current ones.
+    pub fn merge(&mut self, other: Manifest) {
+        self.global = other.global;
+        self.routes.extend(other.routes);
+    }
 }
 
 impl<S: AsRef<OsStr> + ?Sized> From<&S> for Manifest {
@@ -118,9 +132,13 @@ impl ManifestGenerator {
             cache,
         }
     }
-    pub fn generate<H: AsRef<OsStr> + ?Sized>(&self, head: &H) -> Result<Manifest> {
+    pub fn generate<H: AsRef<OsStr> + ?Sized>(
+        &self,
+        head: &H,
+        document: Option<PathBuf>,
+    ) -> Result<Manifest> {
         let cache_path  = self.cache.path();
-        let global = GlobalEntry::new(head, cache_path)?;
+        let global = GlobalEntry::new(head, cache_path, document)?;
         let mut manifest = Manifest::new(global);
 
         for (path, &id) in self.targets.iter() {
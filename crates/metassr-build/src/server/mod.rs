@@ -1,15 +1,20 @@
 pub mod renderer;
 
+mod loader;
 pub mod manifest;
 mod pages_generator;
 mod render;
-mod render_exec;
+pub mod render_exec;
+pub mod render_pool;
 mod targets;
 
 use crate::traits::Build;
-use manifest::ManifestGenerator;
+use manifest::{Manifest, ManifestGenerator};
 
-use metassr_bundler::WebBundler;
+use metassr_bundler::{
+    backends::Bundler, options::BundleOptions, BundleOutput, BundleProfile, MultiBundler,
+    WebBundler,
+};
 use metassr_fs_analyzer::{
     dist_dir::DistDir,
     src_dir::{special_entries, SourceDir},
@@ -18,16 +23,22 @@ use metassr_fs_analyzer::{
 use metassr_utils::cache_dir::CacheDir;
 
 use pages_generator::PagesGenerator;
-use renderer::head::HeadRenderer;
+use renderer::{
+    document::{load_template, DocumentRenderer},
+    head::HeadRenderer,
+};
 
 use std::{
+    collections::{HashMap, HashSet},
     ffi::OsStr,
     fs,
     path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
-use targets::TargetsGenerator;
+use targets::{Targets, TargetsGenerator};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BuildingType {
@@ -39,6 +50,12 @@ pub struct ServerSideBuilder {
     src_path: PathBuf,
     dist_path: PathBuf,
     building_type: BuildingType,
+    pages: Option<HashSet<String>>,
+    options: BundleOptions,
+    revision: u64,
+    ssg_workers: Option<usize>,
+    ssg_timeout: Option<Duration>,
+    backend: Option<Arc<dyn Bundler>>,
 }
 
 impl ServerSideBuilder {
@@ -60,51 +77,240 @@ impl ServerSideBuilder {
             src_path,
             dist_path,
             building_type,
+            pages: None,
+            options: BundleOptions::default(),
+            revision: 0,
+            ssg_workers: None,
+            ssg_timeout: None,
+            backend: None,
         })
     }
+
+    /// Limits the build to `pages`, given as routes of the source directory pages
+    /// (e.g. `blog/index.tsx`). The other pages keep their entries in the existing manifest.
+    /// All pages are built by default.
+    pub fn pages(mut self, pages: HashSet<String>) -> Self {
+        self.pages = Some(pages);
+        self
+    }
+
+    /// Sets the build mode and source maps. Production ones are used by default.
+    pub fn options(mut self, options: BundleOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Mixes `revision` into the IDs of the render functions, which are otherwise derived from
+    /// each page's route and inputs only, so builds of the same sources are identical.
+    ///
+    /// A process loads a render script once per ID: processes that rebuild pages and render
+    /// them again (e.g. `metassr dev`) give each build its own revision, so rebuilt scripts
+    /// are loaded instead of the previous ones.
+    pub fn revision(mut self, revision: u64) -> Self {
+        self.revision = revision;
+        self
+    }
+
+    /// Sets the number of workers rendering the static pages of an SSG build in parallel.
+    /// Defaults to the number of available CPU cores.
+    pub fn ssg_workers(mut self, workers: usize) -> Self {
+        self.ssg_workers = Some(workers);
+        self
+    }
+
+    /// Sets how long a static page of an SSG build can take to render before it fails.
+    /// Defaults to [`DEFAULT_TIMEOUT`](render_pool::DEFAULT_TIMEOUT).
+    pub fn ssg_timeout(mut self, timeout: Duration) -> Self {
+        self.ssg_timeout = Some(timeout);
+        self
+    }
+
+    /// Builds the render scripts, the head and the document with `backend` instead of the
+    /// backend selected by the options.
+    pub fn backend(mut self, backend: Arc<dyn Bundler>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
 }
-// TODO: refactoring build function
-impl Build for ServerSideBuilder {
-    type Output = ();
-    fn build(&self) -> Result<Self::Output> {
+/// The names of the compilations of a server build, bundled together.
+pub(crate) const SERVER: &str = "server";
+const HEAD: &str = "head";
+const DOCUMENT: &str = "document";
+
+/// A server build whose scripts are generated and ready to be bundled.
+pub(crate) struct ServerBuild {
+    cache_dir: CacheDir,
+    head_path: PathBuf,
+    targets: Targets,
+    bundling_targets: HashMap<String, String>,
+    head: HeadRenderer,
+    head_target: HashMap<String, String>,
+    /// The document, with its bundling target if it's a component.
+    document: Option<(DocumentRenderer, Option<HashMap<String, String>>)>,
+}
+
+impl ServerSideBuilder {
+    /// Generates the render scripts of the pages, the head script and the document script.
+    pub(crate) fn prepare(&self) -> Result<ServerBuild> {
         let mut cache_dir = CacheDir::new(&format!("{}/cache", self.dist_path.display()))?;
 
-        let src = SourceDir::new(&self.src_path).analyze()?;
-        let pages = src.clone().pages;
-        let (special_entries::App(app), special_entries::Head(head)) = src.specials()?;
+        let mut src = SourceDir::new(&self.src_path).analyze()?;
+        let (special_entries::App(app), special_entries::Head(head_path)) = src.specials()?;
+        if let Some(only) = &self.pages {
+            src.pages.retain(|page, _| only.contains(page));
+        }
 
-        let targets = match TargetsGenerator::new(app, pages, &mut cache_dir).generate() {
+        let root = self.src_path.parent().unwrap_or(Path::new(""));
+        let targets = match TargetsGenerator::new(app, src.clone(), &mut cache_dir)
+            .root(root)
+            .revision(self.revision)
+            .generate()
+        {
             Ok(t) => t,
             Err(e) => return Err(anyhow!("Couldn't generate targets: {e}")),
         };
-
         let bundling_targets = targets.ready_for_bundling(&self.dist_path);
-        let bundler = WebBundler::new(
-            &bundling_targets,
-            &self.dist_path,
-        )?;
 
-        if let Err(e) = bundler.exec() {
-            return Err(anyhow!("Bundling failed: {e}"));
+        let mut head = HeadRenderer::new(&head_path, cache_dir.clone()).options(self.options);
+        if let Some(backend) = &self.backend {
+            head = head.backend(Arc::clone(backend));
         }
+        let head_target = head.bundling_target()?;
 
+        let document = match src.document() {
+            Some(special_entries::Document(path)) => {
+                let mut document =
+                    DocumentRenderer::new(&path, cache_dir.clone()).options(self.options);
+                if let Some(backend) = &self.backend {
+                    document = document.backend(Arc::clone(backend));
+                }
+                let target = if document.needs_bundling() {
+                    Some(document.bundling_target()?)
+                } else {
+                    None
+                };
+                Some((document, target))
+            }
+            None => None,
+        };
+
+        Ok(ServerBuild {
+            cache_dir,
+            head_path,
+            targets,
+            bundling_targets,
+            head,
+            head_target,
+            document,
+        })
+    }
+
+    /// The bundlers of the render scripts, the head and the document (if it's a component),
+    /// by compilation name.
+    pub(crate) fn bundlers<'a>(
+        &'a self,
+        build: &'a ServerBuild,
+    ) -> Result<Vec<(&'static str, WebBundler<'a>)>> {
+        // The project's dependencies are installed wherever the server runs, so server bundles
+        // `require` them instead of bundling them.
+        let root = self.src_path.parent().unwrap_or(Path::new(""));
+        let server = WebBundler::new(
+            &build.bundling_targets,
+            &self.dist_path,
+            BundleProfile::Server,
+        )?
+        .options(self.options)
+        .cache(&build.cache_dir.path().join("rspack/server"))
+        .externals(root);
+        let mut bundlers = vec![
+            (
+                SERVER,
+                match &self.backend {
+                    Some(backend) => server.backend(Arc::clone(backend)),
+                    None => server,
+                },
+            ),
+            (
+                HEAD,
+                build.head.bundler(&build.head_target)?.externals(root),
+            ),
+        ];
+        if let Some((document, Some(target))) = &build.document {
+            bundlers.push((DOCUMENT, document.bundler(target)?.externals(root)));
+        }
+        Ok(bundlers)
+    }
+
+    /// Renders the document, writes the manifest, and generates the static pages of an SSG
+    /// build, once the scripts of `build` are bundled and the client manifest is written.
+    pub(crate) fn finish(&self, build: ServerBuild) -> Result<()> {
+        let ServerBuild {
+            cache_dir,
+            head_path,
+            targets,
+            mut head,
+            document,
+            ..
+        } = build;
         let dist = DistDir::new(&self.dist_path)?.analyze()?;
 
-        let manifest =
-            ManifestGenerator::new(targets.clone(), cache_dir.clone(), dist).generate(&head)?;
-        manifest.write(&self.dist_path.clone())?;
+        let document = match document {
+            Some((mut document, _)) => Some(
+                document
+                    .render_bundled()
+                    .context("Couldn't render document")?,
+            ),
+            None => None,
+        };
+
+        let mut manifest = ManifestGenerator::new(targets.clone(), cache_dir.clone(), dist)
+            .generate(&head_path, document)?;
 
-        if let Err(e) = HeadRenderer::new(&manifest.global.head, cache_dir.clone()).render(true) {
-            return Err(anyhow!("Coludn't render head: {e}"));
+        // A partial build only updates the entries of the rebuilt pages.
+        if self.pages.is_some() && self.dist_path.join("manifest.json").exists() {
+            let mut previous = Manifest::from(&self.dist_path);
+            previous.merge(manifest);
+            manifest = previous;
         }
+        manifest.write(&self.dist_path.clone())?;
+
+        head.render(false, None).context("Couldn't render head")?;
 
         if self.building_type == BuildingType::StaticSiteGeneration {
-            if let Err(e) =
-                PagesGenerator::new(targets, &head, &self.dist_path, cache_dir)?.generate()
-            {
+            let template = load_template(manifest.global.document.as_deref())?;
+            let mut generator =
+                PagesGenerator::new(targets, &head_path, &self.dist_path, cache_dir, template)?;
+            if let Some(workers) = self.ssg_workers {
+                generator = generator.workers(workers);
+            }
+            if let Some(timeout) = self.ssg_timeout {
+                generator = generator.timeout(timeout);
+            }
+            if let Err(e) = generator.generate() {
                 return Err(anyhow!("Couldn't generate pages: {e}"));
             }
         }
         Ok(())
     }
 }
+
+impl Build for ServerSideBuilder {
+    /// The bundled files and modules of each page's render script.
+    type Output = BundleOutput;
+    fn build(&self) -> Result<Self::Output> {
+        let build = self.prepare()?;
+
+        // The render scripts, head and document are bundled at once. Bundling errors are
+        // returned as is, so their diagnostics reach the caller.
+        let mut outputs = self
+            .bundlers(&build)?
+            .into_iter()
+            .fold(MultiBundler::new(), |bundler, (name, compilation)| {
+                bundler.add(name, compilation)
+            })
+            .exec()?;
+
+        self.finish(build)?;
+        Ok(outputs.remove(SERVER).unwrap_or_default())
+    }
+}
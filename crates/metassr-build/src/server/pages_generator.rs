@@ -1,28 +1,36 @@
 use std::{
-    collections::HashMap,
     ffi::OsStr,
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Result};
+use html_generator::template::HtmlTemplate;
 use metassr_fs_analyzer::{
     dist_dir::{DistDir, DistDirContainer},
     DirectoryAnalyzer,
 };
 use metassr_utils::cache_dir::CacheDir;
-
-use crate::traits::Exec;
+use serde_json::{Map, Value};
+use tracing::{error, info};
 
 use super::{
-    render_exec::MultiRenderExec, renderer::head::HeadRenderer, renderer::html::HtmlRenderer,
+    render_exec::RenderOutput,
+    render_pool::{RenderPool, DEFAULT_TIMEOUT},
+    renderer::head::HeadRenderer,
+    renderer::html::HtmlRenderer,
     targets::Targets,
 };
 
 pub struct PagesGenerator {
+    template: HtmlTemplate,
     cache: PathBuf,
     dist: DistDirContainer,
-    head: String,
-    output: HashMap<String, String>,
+    head: HeadRenderer,
+    props: Value,
+    targets: Vec<(PathBuf, i64)>,
+    workers: usize,
+    timeout: Duration,
 }
 
 impl PagesGenerator {
@@ -31,45 +39,115 @@ impl PagesGenerator {
         head_path: &S,
         dist_path: &S,
         cache_dir: CacheDir,
+        template: HtmlTemplate,
     ) -> Result<Self> {
         let dist = DistDir::new(dist_path)?.analyze()?;
-        let head = HeadRenderer::new(&head_path, cache_dir.clone()).render(true)?;
+        let head = HeadRenderer::new(&head_path, cache_dir.clone());
         let cache = cache_dir.path().to_path_buf();
 
-        let output = MultiRenderExec::new(targets.ready_for_exec())?.exec()?;
+        // Pages are rendered in the order of their paths, so the progress is the same across builds.
+        let mut targets: Vec<(PathBuf, i64)> = targets
+            .iter()
+            .map(|(path, &id)| (path.clone(), id))
+            .collect();
+        targets.sort();
+        if let Some((path, _)) = targets.iter().find(|(path, _)| !path.exists()) {
+            return Err(anyhow!("Path not found: {path:#?}"));
+        }
 
         Ok(Self {
+            template,
             dist,
             head,
             cache,
-            output,
+            // Static pages are generated at build time, so there are no request-specific props.
+            props: Value::Object(Map::new()),
+            targets,
+            workers: RenderPool::default_workers(),
+            timeout: DEFAULT_TIMEOUT,
         })
     }
 
-    pub fn generate(&self) -> Result<()> {
-        for (path, html_body) in &self.output {
-            let path = Path::new(&path).parent().unwrap();
-            // dbg!(&path, &self.cache.join(""));
+    /// Sets the number of workers rendering the pages in parallel. Defaults to the number of
+    /// available CPU cores.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Sets how long a page can take to render before it fails (see [`RenderPool::timeout`]).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Renders the pages across a pool of workers, and writes them to their entries in the dist
+    /// directory. A page that fails doesn't stop the others: every failure is logged, then
+    /// reported in the returned error.
+    pub fn generate(&mut self) -> Result<()> {
+        let instant = Instant::now();
+        let total = self.targets.len();
+        let workers = self.workers.min(total.max(1));
+
+        // There are no route parameters either.
+        let params = Value::Object(Map::new());
+        let outputs = RenderPool::new(workers).timeout(self.timeout).render(
+            &self.targets,
+            &self.props,
+            &params,
+            |done, total| {
+                info!(
+                    target = "ssg",
+                    message = format!("Rendered {done}/{total} page(s)"),
+                    time = format!("{}ms", instant.elapsed().as_millis())
+                );
+            },
+        )?;
+
+        let mut failures = vec![];
+        for (i, output) in outputs.into_iter().enumerate() {
+            let path = self.targets[i].0.parent().unwrap();
             let route = match path.strip_prefix(self.cache.join("pages"))? {
-                p if p == Path::new("") => "#root",
-                p => p.to_str().unwrap(),
+                p if p == Path::new("") => "#root".to_owned(),
+                p => p.to_str().unwrap().to_owned(),
             };
-
-            let page_entry = self.dist.pages.get(route);
-            match page_entry {
-                Some(page_entry) => {
-                    // dbg!(&path.join("index.html"));
-                    HtmlRenderer::new(&self.head, html_body, page_entry)
-                        .render()?
-                        .write(page_entry.path.join("index.html"))?;
-                }
-                None => {
-                    return Err(anyhow!(
-                    "ssg: No Entries founded for this page: route = {route:#?}, path = {path:#?}",
-                ))
-                }
+            if let Err(e) = output.and_then(|output| self.write(&route, &output)) {
+                failures.push((route, e));
             }
         }
+
+        info!(
+            target = "ssg",
+            message = format!(
+                "Generated {} of {total} page(s) with {workers} worker(s)",
+                total - failures.len()
+            ),
+            time = format!("{}ms", instant.elapsed().as_millis())
+        );
+        if failures.is_empty() {
+            return Ok(());
+        }
+        for (route, e) in &failures {
+            error!(
+                target = "ssg",
+                message = format!("Couldn't generate page {route:?}: {e}")
+            );
+        }
+        Err(anyhow!(
+            "{} of {total} page(s) failed to generate",
+            failures.len()
+        ))
+    }
+
+    /// Writes the page of `route` rendered as `output`.
+    fn write(&mut self, route: &str, output: &RenderOutput) -> Result<()> {
+        let Some(page_entry) = self.dist.pages.get(route) else {
+            return Err(anyhow!("No entries found for this page"));
+        };
+        let head = self.head.render(true, Some(&output.head))?;
+        HtmlRenderer::new(&self.template, &head, &output.body, &self.props, page_entry)
+            .render()?
+            .write(page_entry.path.join("index.html"))?;
         Ok(())
     }
 }
@@ -1,47 +1,103 @@
 use crate::{
-    shared::{APP_PATH_TAG, FUNC_ID_TAG, PAGE_PATH_TAG},
+    shared::{
+        APP_PATH_TAG, FUNC_ID_TAG, HEAD_TAGS_PATH_TAG, LAYOUTS_IMPORTS_TAG, LAYOUTS_TAG,
+        LAYOUT_MODULES_TAG, PAGE_PATH_TAG,
+    },
     traits::Generate,
+    utils::{layout_modules, setup_layouts},
 };
 use anyhow::Result;
-use metassr_utils::rand::Rand;
-use std::{ffi::OsStr, path::PathBuf};
+use metassr_utils::hash::content_hash;
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
 
 const RENDER_FILE_TEMPLATE: &str = include_str!("../scripts/render.js.template");
 
+/// Returns the ID of a page's render functions: a stable hash of the page's route, its render
+/// script (which imports the page, `_app` and the layouts, with their paths relative to the
+/// project root) and the build's revision, so building the same sources twice names them the
+/// same, wherever the project is.
+fn render_id(route: &str, script: &str, revision: u64) -> i64 {
+    let hash = content_hash(format!("{route}\n{revision}\n{script}").as_bytes());
+    // 15 hexadecimal digits (60 bits) always fit in a non-negative `i64`
+    i64::from_str_radix(&hash[..15], 16).unwrap()
+}
+
 pub struct ServerRender {
+    route: String,
     app_path: PathBuf,
     page_path: PathBuf,
+    layouts: Vec<PathBuf>,
+    head_tags: PathBuf,
+    root: PathBuf,
+    revision: u64,
 }
 
 impl ServerRender {
-    pub fn new<'a, S>(app_path: &'a S, page_path: &'a S) -> Self
+    /// `route` is the page's route in the source directory (e.g. `blog/index.tsx`), and
+    /// `layouts` are the layouts wrapping the page, from the outermost to the innermost.
+    pub fn new<'a, S>(route: &str, app_path: &'a S, page_path: &'a S, layouts: &[PathBuf]) -> Self
     where
         S: AsRef<OsStr> + ?Sized,
     {
         Self {
+            route: route.to_owned(),
             app_path: PathBuf::from(app_path),
             page_path: PathBuf::from(page_path),
+            layouts: layouts.to_vec(),
+            head_tags: PathBuf::new(),
+            root: PathBuf::new(),
+            revision: 0,
         }
     }
+
+    /// Sets the path of the module collecting the tags of the `head` exports, written by
+    /// [`write_head_tags`](super::renderer::head::write_head_tags).
+    pub fn head_tags<P: AsRef<Path> + ?Sized>(mut self, path: &P) -> Self {
+        self.head_tags = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Sets the project root, the current directory by default. The ID of the render functions
+    /// is computed from the paths relative to it.
+    pub fn root<P: AsRef<Path> + ?Sized>(mut self, root: &P) -> Self {
+        self.root = root.as_ref().to_path_buf();
+        self
+    }
+
+    /// Mixes `revision` into the ID of the render functions (see
+    /// [`ServerSideBuilder::revision`](super::ServerSideBuilder::revision)).
+    pub fn revision(mut self, revision: u64) -> Self {
+        self.revision = revision;
+        self
+    }
 }
 
 impl Generate for ServerRender {
     type Output = (i64, String);
     fn generate(&self) -> Result<Self::Output> {
-        let func_id = Rand::new().val();
         let mut app_path = self.app_path.canonicalize()?;
         let mut page_path = self.page_path.canonicalize()?;
 
         app_path.set_extension("");
         page_path.set_extension("");
+        let (layouts_imports, layouts) = setup_layouts(&self.layouts, false)?;
 
-        Ok((
-            func_id,
-            RENDER_FILE_TEMPLATE
-                .replace(APP_PATH_TAG, app_path.to_str().unwrap())
-                .replace(PAGE_PATH_TAG, page_path.to_str().unwrap())
-                .replace(FUNC_ID_TAG, &func_id.to_string()),
-        ))
+        let script = RENDER_FILE_TEMPLATE
+            .replace(APP_PATH_TAG, app_path.to_str().unwrap())
+            .replace(PAGE_PATH_TAG, page_path.to_str().unwrap())
+            .replace(HEAD_TAGS_PATH_TAG, self.head_tags.to_str().unwrap())
+            .replace(LAYOUTS_IMPORTS_TAG, &layouts_imports)
+            .replace(LAYOUTS_TAG, &layouts)
+            .replace(LAYOUT_MODULES_TAG, &layout_modules(self.layouts.len()));
+        // An empty root is the current directory
+        let root = Path::new(".").join(&self.root).canonicalize()?;
+        let portable = script.replace(root.to_str().unwrap(), "<root>");
+        let func_id = render_id(&self.route, &portable, self.revision);
+
+        Ok((func_id, script.replace(FUNC_ID_TAG, &func_id.to_string())))
     }
 }
 
@@ -52,9 +108,23 @@ mod tests {
     fn generate_render_file() {
         println!(
             "{:?}",
-            ServerRender::new("src/_app.tsx", "src/pages/home.jsx")
+            ServerRender::new("home.jsx", "src/_app.tsx", "src/pages/home.jsx", &[])
                 .generate()
                 .unwrap()
         );
     }
+
+    #[test]
+    fn render_ids_are_stable() {
+        let script = "export function render_%FUNC_ID%() {}";
+        let id = render_id("home.jsx", script, 0);
+        assert_eq!(id, render_id("home.jsx", script, 0));
+        assert!(id >= 0);
+        assert_ne!(id, render_id("about.jsx", script, 0));
+        assert_ne!(id, render_id("home.jsx", script, 1));
+        assert_ne!(
+            id,
+            render_id("home.jsx", "export function render_%FUNC_ID%(props) {}", 0)
+        );
+    }
 }
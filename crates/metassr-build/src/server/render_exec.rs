@@ -1,22 +1,87 @@
 use std::{
     collections::HashMap,
+    error::Error,
     ffi::OsStr,
+    fmt::Display,
     path::{Path, PathBuf},
 };
 
+use super::loader;
 use crate::traits::Exec;
 use anyhow::{anyhow, Result};
-use metacall::{loaders, metacall_no_arg};
+use metassr_utils::code_frame::SourceLocation;
+use serde::Deserialize;
+use serde_json::Value;
 
 const RENDER_FUNC_PREFIX: &str = "render_";
+const PAGE_HEAD_FUNC_PREFIX: &str = "page_head_";
+
+/// The result of rendering a page.
+#[derive(Debug, Clone)]
+pub struct RenderOutput {
+    /// The rendered page body.
+    pub body: String,
+    /// The page's own head tags (from its `head`/`metadata` exports) as JSON, ready to be
+    /// merged with the global head by [`HeadRenderer`](super::renderer::head::HeadRenderer).
+    pub head: String,
+}
+
+/// An error thrown by a page's code while rendering it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenderError {
+    pub message: String,
+    /// The JavaScript stack trace of the error.
+    pub stack: String,
+}
+
+impl RenderError {
+    /// Locates the error in the project's sources, using the first stack frame that points
+    /// to an existing file outside of `node_modules`.
+    pub fn location(&self) -> Option<SourceLocation> {
+        self.stack
+            .lines()
+            .filter_map(SourceLocation::from_stack_frame)
+            .find(|location| {
+                !location
+                    .file
+                    .components()
+                    .any(|c| c.as_os_str() == "node_modules")
+                    && location.file.exists()
+            })
+    }
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location() {
+            Some(location) => write!(f, "{}\n  at {location}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+/// What the render functions of a script return: their result, or the error they threw.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Guarded<T> {
+    Ok(T),
+    Error(RenderError),
+}
 
 pub struct RenderExec {
     id: i64,
     path: PathBuf,
+    props: Value,
+    params: Value,
 }
 
 impl RenderExec {
-    pub fn new<S>(id: i64, path: &S) -> Result<Self>
+    /// `props` are passed to the page while rendering, and must be embedded in the resulting
+    /// HTML, so the client hydrates with the same data. `params` are the route parameters,
+    /// which are only used for the page head.
+    pub fn new<S>(id: i64, path: &S, props: &Value, params: &Value) -> Result<Self>
     where
         S: AsRef<OsStr> + ?Sized,
     {
@@ -27,37 +92,61 @@ impl RenderExec {
         Ok(Self {
             id,
             path: path.to_path_buf(),
+            props: props.clone(),
+            params: params.clone(),
         })
     }
 }
 
 impl Exec for RenderExec {
-    type Output = String;
+    type Output = RenderOutput;
     fn exec(&self) -> Result<Self::Output> {
-        // let path = self.0.strip_prefix(current_dir()?)?;
-        if let Err(e) = loaders::from_single_file("node", &self.path) {
-            return Err(anyhow!(
-                "Cannot load render script: {e:?} \n  path: {:#?}",
-                self.path
-            ));
-        }
+        let body: String = self.call(RENDER_FUNC_PREFIX, [self.props.to_string()])?;
+        let head: Value = self.call(
+            PAGE_HEAD_FUNC_PREFIX,
+            [self.props.to_string(), self.params.to_string()],
+        )?;
 
-        match metacall_no_arg::<String>(format!("{}{}", RENDER_FUNC_PREFIX, self.id)) {
-            Err(e) => Err(anyhow!(
-                "Cannot running {RENDER_FUNC_PREFIX}{}(): {e:?}",
-                self.id
-            )),
-            Ok(out) => Ok(out),
-        }
+        Ok(RenderOutput {
+            body,
+            head: head.to_string(),
+        })
+    }
+}
+
+impl RenderExec {
+    /// Calls the `prefix` function of the render script, which returns a guarded JSON result.
+    ///
+    /// The script is loaded under its ID: a render script rebuilt with a new revision gets a
+    /// new ID, so it's loaded again from the same path (see `ServerSideBuilder::revision`).
+    fn call<T, const N: usize>(&self, prefix: &str, args: [String; N]) -> Result<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let name = format!("{prefix}{}", self.id);
+        let out = loader::call(&self.path, &self.id.to_string(), &name, args)?;
+        guarded(&out)
+    }
+}
+
+/// Parses what a render function returned: its JSON result, or the error it threw.
+pub(crate) fn guarded<T>(out: &str) -> Result<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    match serde_json::from_str(out)? {
+        Guarded::Ok(value) => Ok(value),
+        Guarded::Error(e) => Err(e.into()),
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct MultiRenderExec(HashMap<PathBuf, i64>);
+pub struct MultiRenderExec(HashMap<PathBuf, i64>, Value, Value);
 
 impl MultiRenderExec {
-    pub fn new(files: HashMap<String, i64>) -> Result<Self> {
-        let mut self_ = Self(HashMap::new());
+    /// Every file is rendered with the same `props` and `params`.
+    pub fn new(files: HashMap<String, i64>, props: &Value, params: &Value) -> Result<Self> {
+        let mut self_ = Self(HashMap::new(), props.clone(), params.clone());
 
         for (path, id) in files {
             let path = Path::new(&path);
@@ -71,13 +160,13 @@ impl MultiRenderExec {
 }
 
 impl Exec for MultiRenderExec {
-    type Output = HashMap<String, String>;
+    type Output = HashMap<String, RenderOutput>;
     fn exec(&self) -> Result<Self::Output> {
         let mut result: Self::Output = HashMap::new();
 
         for (path, id) in self.0.iter() {
             let path = path.to_str().unwrap();
-            let out = RenderExec::new(*id, &path)?.exec()?;
+            let out = RenderExec::new(*id, &path, &self.1, &self.2)?.exec()?;
             result.insert(path.to_owned(), out);
         }
         Ok(result)
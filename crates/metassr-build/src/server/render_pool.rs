@@ -0,0 +1,255 @@
+use std::{path::PathBuf, sync::Mutex, time::Duration};
+
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use metacall::{loaders, metacall, metacall_untyped_no_arg};
+use metassr_utils::checker::CheckerState;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::render_exec::{guarded, RenderError, RenderOutput};
+
+const RENDER_POOL_SCRIPT: &str = include_str!("../scripts/render_pool.js");
+
+lazy_static! {
+    static ref IS_RENDER_POOL_LOADED: Mutex<CheckerState> = Mutex::new(CheckerState::default());
+}
+
+/// Pages sent to the workers per call: the progress is reported after each batch.
+const PAGES_PER_WORKER: usize = 8;
+
+/// How long a page can take to render by default, before its worker is stopped.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A page to render: its render script and the ID of its render functions.
+#[derive(Serialize)]
+struct Job<'a> {
+    file: &'a str,
+    id: i64,
+    props: &'a str,
+    params: &'a str,
+}
+
+#[derive(Serialize)]
+struct Batch<'a> {
+    workers: usize,
+    /// The timeout of each page, in milliseconds.
+    timeout: u128,
+    jobs: &'a [Job<'a>],
+}
+
+/// What a worker reported for a page: the guarded results of its render functions, or the
+/// error that prevented calling them (e.g. a render script that couldn't be loaded).
+#[derive(Deserialize)]
+struct JobResult {
+    render: Option<String>,
+    head: Option<String>,
+    error: Option<RenderError>,
+}
+
+impl JobResult {
+    fn output(self) -> Result<RenderOutput> {
+        if let Some(e) = self.error {
+            return Err(e.into());
+        }
+        let (Some(render), Some(head)) = (self.render, self.head) else {
+            return Err(anyhow!("The render worker returned no result"));
+        };
+        let head: Value = guarded(&head)?;
+        Ok(RenderOutput {
+            body: guarded(&render)?,
+            head: head.to_string(),
+        })
+    }
+}
+
+/// `RenderPool` renders pages across a pool of Node worker threads, each one an isolated
+/// runtime loading its own copy of the render scripts, so pages render in parallel.
+///
+/// Each page gets its own result: a page that fails to render doesn't stop the others, even
+/// one that never returns, which fails once its timeout is reached.
+///
+/// # Example
+///
+/// ```no_run
+/// use metassr_build::server::render_pool::RenderPool;
+/// use serde_json::json;
+/// use std::path::PathBuf;
+///
+/// let _metacall = metacall::switch::initialize().unwrap();
+/// let pages = [(PathBuf::from("dist/cache/pages/index.server.js"), 42)];
+/// let outputs = RenderPool::new(4)
+///     .render(&pages, &json!({}), &json!({}), |done, total| println!("{done}/{total}"))
+///     .unwrap();
+/// ```
+pub struct RenderPool {
+    workers: usize,
+    timeout: Duration,
+}
+
+impl RenderPool {
+    /// Creates a pool of `workers` workers, started on the first render.
+    pub fn new(workers: usize) -> Self {
+        Self {
+            workers: workers.max(1),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets how long a page can take to render. The worker rendering a page past its timeout
+    /// is stopped and replaced, and the page fails. Defaults to [`DEFAULT_TIMEOUT`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns a worker per available CPU core.
+    pub fn default_workers() -> usize {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    }
+
+    /// Renders `pages` (render scripts with the ID of their functions) with the same `props`
+    /// and `params`, calling `progress` with the number of rendered pages and the total after
+    /// each batch. Returns the output or the error of each page, in the order of `pages`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the pool itself can't run; errors of the pages are returned with their results.
+    pub fn render<F>(
+        &self,
+        pages: &[(PathBuf, i64)],
+        props: &Value,
+        params: &Value,
+        mut progress: F,
+    ) -> Result<Vec<Result<RenderOutput>>>
+    where
+        F: FnMut(usize, usize),
+    {
+        let mut guard = IS_RENDER_POOL_LOADED.lock().unwrap();
+        if !guard.is_true() {
+            if let Err(e) = loaders::from_memory("node", RENDER_POOL_SCRIPT) {
+                return Err(anyhow!("Cannot load the render pool script: {e:?}"));
+            }
+            guard.make_true();
+        }
+        drop(guard);
+
+        // The workers are stopped whether the pages rendered or not
+        let outputs = self.render_batches(pages, props, params, &mut progress);
+        let _ = metacall_untyped_no_arg("stop_render_pool");
+        outputs
+    }
+
+    /// Renders `pages` like `render`, in batches, once the render pool script is loaded.
+    fn render_batches<F>(
+        &self,
+        pages: &[(PathBuf, i64)],
+        props: &Value,
+        params: &Value,
+        progress: &mut F,
+    ) -> Result<Vec<Result<RenderOutput>>>
+    where
+        F: FnMut(usize, usize),
+    {
+        let (props, params) = (props.to_string(), params.to_string());
+        let mut outputs = Vec::with_capacity(pages.len());
+        for batch in pages.chunks(self.workers * PAGES_PER_WORKER) {
+            let jobs: Vec<Job> = batch
+                .iter()
+                .map(|(path, id)| Job {
+                    file: path.to_str().unwrap(),
+                    id: *id,
+                    props: &props,
+                    params: &params,
+                })
+                .collect();
+            let batch = serde_json::to_string(&Batch {
+                workers: self.workers,
+                timeout: self.timeout.as_millis(),
+                jobs: &jobs,
+            })?;
+
+            let results = match metacall::<String>("render_pages", [batch]) {
+                Ok(results) => results,
+                Err(e) => return Err(anyhow!("Cannot run the render pool: {e:?}")),
+            };
+            let results: Vec<JobResult> = serde_json::from_str(&results)?;
+            outputs.extend(results.into_iter().map(JobResult::output));
+            progress(outputs.len(), pages.len());
+        }
+        Ok(outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::Path;
+
+    #[test]
+    fn parsing_job_results() {
+        let results: Vec<JobResult> = serde_json::from_str(
+            r#"[
+                {"index": 0, "render": "{\"ok\":\"<h1>Home</h1>\"}", "head": "{\"ok\":{\"title\":\"Home\"}}"},
+                {"index": 1, "render": "{\"error\":{\"message\":\"boom\",\"stack\":\"\"}}", "head": "{\"ok\":{}}"},
+                {"index": 2, "error": {"message": "Cannot find module", "stack": ""}}
+            ]"#,
+        )
+        .unwrap();
+        let mut outputs = results.into_iter().map(JobResult::output);
+
+        let output = outputs.next().unwrap().unwrap();
+        assert_eq!(output.body, "<h1>Home</h1>");
+        assert_eq!(output.head, r#"{"title":"Home"}"#);
+        assert_eq!(outputs.next().unwrap().unwrap_err().to_string(), "boom");
+        assert_eq!(
+            outputs.next().unwrap().unwrap_err().to_string(),
+            "Cannot find module"
+        );
+    }
+
+    #[test]
+    fn pages_killing_their_worker_fail() {
+        let _metacall = metacall::switch::initialize().unwrap();
+        let page = |name: &str| {
+            let path = Path::new("../../tests/render-pool").join(name);
+            (path.canonicalize().unwrap(), 1)
+        };
+        let pages = ["page.js", "async-throw.js", "page.js", "exit.js", "page.js"].map(page);
+
+        // The pool replaces the workers the pages killed, and renders the other pages
+        let outputs = RenderPool::new(2)
+            .render(&pages, &json!({}), &json!({}), |_, _| {})
+            .unwrap();
+        assert_eq!(outputs.len(), pages.len());
+        for i in [0, 2, 4] {
+            assert_eq!(outputs[i].as_ref().unwrap().body, "<h1>Page</h1>");
+        }
+        let error = |i: usize| outputs[i].as_ref().unwrap_err().to_string();
+        assert!(error(1).contains("Thrown asynchronously"));
+        assert!(error(3).contains("exited the process"));
+    }
+
+    #[test]
+    fn pages_never_returning_time_out() {
+        let _metacall = metacall::switch::initialize().unwrap();
+        let page = |name: &str| {
+            let path = Path::new("../../tests/render-pool").join(name);
+            (path.canonicalize().unwrap(), 1)
+        };
+        let pages = ["page.js", "hang.js", "page.js"].map(page);
+
+        let outputs = RenderPool::new(2)
+            .timeout(Duration::from_millis(500))
+            .render(&pages, &json!({}), &json!({}), |_, _| {})
+            .unwrap();
+        assert_eq!(outputs[0].as_ref().unwrap().body, "<h1>Page</h1>");
+        assert!(outputs[1]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("didn't render within 500ms"));
+        assert_eq!(outputs[2].as_ref().unwrap().body, "<h1>Page</h1>");
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::{anyhow, Context, Result};
+use html_generator::template::HtmlTemplate;
+use metassr_utils::cache_dir::CacheDir;
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use metassr_bundler::{
+    backends::Bundler, default_public_path, options::BundleOptions, BundleProfile, WebBundler,
+};
+
+use crate::server::loader;
+
+/// Renders the project's custom document (`src/_document.[html, jsx, tsx]`) into an HTML
+/// template stored in the cache directory, so pages can be built from it.
+pub struct DocumentRenderer {
+    path: PathBuf,
+    cache_dir: CacheDir,
+    options: BundleOptions,
+    backend: Option<Arc<dyn Bundler>>,
+}
+
+impl DocumentRenderer {
+    pub fn new<S>(path: &S, cache_dir: CacheDir) -> Self
+    where
+        S: AsRef<OsStr> + ?Sized,
+    {
+        Self {
+            path: PathBuf::from(path),
+            cache_dir,
+            options: BundleOptions::default(),
+            backend: None,
+        }
+    }
+
+    /// Sets the build mode and source maps of the document bundle.
+    pub fn options(mut self, options: BundleOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Builds the document bundle with `backend` instead of the backend selected by the options.
+    pub fn backend(mut self, backend: Arc<dyn Bundler>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Renders and validates the document, returning the path of the generated template.
+    pub fn render(&mut self) -> Result<PathBuf> {
+        if self.needs_bundling() {
+            self.bundle()?;
+        }
+        self.render_bundled()
+    }
+
+    /// Whether the document is a component, which has to be bundled before it's rendered.
+    pub(crate) fn needs_bundling(&self) -> bool {
+        self.path.extension().is_none_or(|ext| ext != "html")
+    }
+
+    /// Renders the document like `render`, once its script has already been bundled.
+    ///
+    /// The document script is loaded again whenever it's rebuilt, so each build renders the
+    /// latest document.
+    pub(crate) fn render_bundled(&mut self) -> Result<PathBuf> {
+        let document = if self.needs_bundling() {
+            let script = self.cache_dir.path().join("document.js");
+            let out = loader::call(&script, &loader::modified(&script)?, "render_document", [])
+                .context("Couldn't render document")?;
+            format!("<!DOCTYPE html>\n{out}")
+        } else {
+            read_to_string(&self.path)?
+        };
+
+        if let Err(e) = HtmlTemplate::new(&document).validate() {
+            return Err(anyhow!("Invalid document {:#?}: {e}", self.path));
+        }
+
+        self.cache_dir.insert("document.html", document.as_bytes())
+    }
+
+    fn bundle(&mut self) -> Result<()> {
+        let bundling_targets = self.bundling_target()?;
+        // The bundling error is kept as is, so its diagnostics reach the caller.
+        self.bundler(&bundling_targets)?
+            .exec()
+            .context("Cannot bundle document")?;
+        Ok(())
+    }
+
+    /// The bundler of the document script, from the targets returned by `bundling_target`.
+    pub(crate) fn bundler<'a>(
+        &'a self,
+        bundling_targets: &'a HashMap<String, String>,
+    ) -> Result<WebBundler<'a>> {
+        let bundler = WebBundler::new(
+            bundling_targets,
+            self.cache_dir.path(),
+            BundleProfile::Server,
+        )?
+        .options(self.options)
+        .cache(&self.cache_dir.path().join("rspack/document"))
+        // Emitted in the cache directory, it references the assets of the dist directory above
+        .public_path(&default_public_path(
+            self.cache_dir.path().parent().unwrap_or(Path::new("")),
+        ));
+        Ok(match &self.backend {
+            Some(backend) => bundler.backend(Arc::clone(backend)),
+            None => bundler,
+        })
+    }
+
+    fn script(&self) -> Result<String> {
+        let script = format!(
+            r#"
+import Document from "{}"
+import {{ renderToStaticMarkup }} from "react-dom/server"
+import React from "react"
+
+export function render_document() {{
+    return renderToStaticMarkup(<Document />);
+}}
+"#,
+            self.path.canonicalize()?.display()
+        );
+        Ok(script)
+    }
+
+    /// Writes the document script to the cache directory and returns it as a bundling target.
+    pub(crate) fn bundling_target(&mut self) -> Result<HashMap<String, String>> {
+        let path = self
+            .cache_dir
+            .insert("document.js", self.script()?.as_bytes())?;
+        let fullpath = path.canonicalize()?.to_str().unwrap().to_string();
+
+        Ok(HashMap::from([("document".to_string(), fullpath)]))
+    }
+}
+
+/// Loads the HTML template pages are built from: the rendered custom document if there is one,
+/// or the default template otherwise.
+pub fn load_template(document: Option<&Path>) -> Result<HtmlTemplate> {
+    match document {
+        Some(path) => {
+            let template = HtmlTemplate::new(&read_to_string(path)?);
+            template.validate()?;
+            Ok(template)
+        }
+        None => Ok(HtmlTemplate::default()),
+    }
+}
@@ -1,18 +1,35 @@
-use anyhow::{anyhow, Result};
+use anyhow::{Context, Result};
 use lazy_static::lazy_static;
-use metacall::{loaders, metacall_no_arg};
 use metassr_utils::{cache_dir::CacheDir, checker::CheckerState};
-use std::{collections::HashMap, ffi::OsStr, path::PathBuf, sync::Mutex};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
-use metassr_bundler::WebBundler;
+use metassr_bundler::{
+    backends::Bundler, default_public_path, options::BundleOptions, BundleProfile, WebBundler,
+};
+
+use crate::{
+    server::loader,
+    shared::{HEAD_PATH_TAG, HEAD_TAGS_PATH_TAG},
+};
+
+const HEAD_FILE_TEMPLATE: &str = include_str!("../../scripts/head.js.template");
+const HEAD_TAGS_SCRIPT: &str = include_str!("../../scripts/head_tags.js");
 
 lazy_static! {
-    static ref IS_HEAD_SCRIPT_LOADED: Mutex<CheckerState> = Mutex::new(CheckerState::default());
+    /// Whether the head was rendered already, so it's only bundled for the first render.
+    static ref IS_HEAD_RENDERED: Mutex<CheckerState> = Mutex::new(CheckerState::default());
 }
 
 pub struct HeadRenderer {
     path: PathBuf,
     cache_dir: CacheDir,
+    options: BundleOptions,
+    backend: Option<Arc<dyn Bundler>>,
 }
 
 impl HeadRenderer {
@@ -23,61 +40,89 @@ impl HeadRenderer {
         Self {
             path: PathBuf::from(path),
             cache_dir,
+            options: BundleOptions::default(),
+            backend: None,
         }
     }
 
-    pub fn render(&mut self, bundler: bool) -> Result<String> {
-        let mut guard = IS_HEAD_SCRIPT_LOADED.lock().unwrap();
-        if !guard.is_true() {
-            if bundler {
-                self.bundle()?;
-            }
-
-            let _ = loaders::from_single_file(
-                "node",
-                format!("{}/head.js", self.cache_dir.path().display()),
-            );
-            guard.make_true()
+    /// Sets the build mode and source maps of the head bundle.
+    pub fn options(mut self, options: BundleOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Builds the head bundle with `backend` instead of the backend selected by the options.
+    pub fn backend(mut self, backend: Arc<dyn Bundler>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Renders the global `_head`, merged with `page_head`: the page's own head tags as
+    /// returned by its render script. Page tags override global tags with the same `key`,
+    /// `name` or `property`.
+    ///
+    /// The head script is loaded again whenever it's rebuilt, so changes to `_head` apply
+    /// without a restart.
+    pub fn render(&mut self, bundler: bool, page_head: Option<&str>) -> Result<String> {
+        let mut guard = IS_HEAD_RENDERED.lock().unwrap();
+        if bundler && !guard.is_true() {
+            self.bundle()?;
         }
+        guard.make_true();
         drop(guard);
 
-        match metacall_no_arg::<String>("render_head") {
-            Err(e) => Err(anyhow!("Couldn't render head: {e:?}")),
-            Ok(out) => Ok(out),
-        }
+        let script = self.cache_dir.path().join("head.js");
+        loader::call(
+            &script,
+            &loader::modified(&script)?,
+            "render_head",
+            [page_head.unwrap_or("[]").to_string()],
+        )
+        .context("Couldn't render head")
     }
 
     fn bundle(&mut self) -> Result<()> {
         let bundling_targets = self.bundling_target()?;
-        let bundler = WebBundler::new(&bundling_targets, self.cache_dir.path())?;
-
-        if let Err(e) = bundler.exec()
-        {
-            return Err(anyhow!("Cannot bundling head: {e}"));
-        }
+        // The bundling error is kept as is, so its diagnostics reach the caller.
+        self.bundler(&bundling_targets)?
+            .exec()
+            .context("Cannot bundle head")?;
         Ok(())
     }
-    fn script(&self) -> Result<String> {
-        let script = format!(
-            r#"
-import Head from "{}"
-import {{ renderToString }} from "react-dom/server"
-import React from "react"
-
-export function render_head() {{
-    return renderToString(<Head />);
-}}            
-                
-                "#,
-            self.path.canonicalize()?.display()
-        );
-        Ok(script)
+
+    /// The bundler of the head script, from the targets returned by `bundling_target`.
+    pub(crate) fn bundler<'a>(
+        &'a self,
+        bundling_targets: &'a HashMap<String, String>,
+    ) -> Result<WebBundler<'a>> {
+        let bundler = WebBundler::new(
+            bundling_targets,
+            self.cache_dir.path(),
+            BundleProfile::Server,
+        )?
+        .options(self.options)
+        .cache(&self.cache_dir.path().join("rspack/head"))
+        // Emitted in the cache directory, it references the assets of the dist directory above
+        .public_path(&default_public_path(
+            self.cache_dir.path().parent().unwrap_or(Path::new("")),
+        ));
+        Ok(match &self.backend {
+            Some(backend) => bundler.backend(Arc::clone(backend)),
+            None => bundler,
+        })
     }
 
-    fn bundling_target(&mut self) -> Result<HashMap<String, String>> {
-        let path = self
-            .cache_dir
-            .insert("head.js", self.script()?.as_bytes())?;
+    fn script(&self, head_tags: &Path) -> Result<String> {
+        Ok(HEAD_FILE_TEMPLATE
+            .replace(HEAD_PATH_TAG, self.path.canonicalize()?.to_str().unwrap())
+            .replace(HEAD_TAGS_PATH_TAG, head_tags.to_str().unwrap()))
+    }
+
+    /// Writes the head script to the cache directory and returns it as a bundling target.
+    pub(crate) fn bundling_target(&mut self) -> Result<HashMap<String, String>> {
+        let head_tags = write_head_tags(&mut self.cache_dir)?;
+        let script = self.script(&head_tags)?;
+        let path = self.cache_dir.insert("head.js", script.as_bytes())?;
         let name = PathBuf::from(path.clone().file_name().unwrap())
             .with_extension("")
             .to_str()
@@ -88,3 +133,11 @@ export function render_head() {{
         Ok(HashMap::from([(name, fullpath)]))
     }
 }
+
+/// Writes the module collecting the tags of head trees, imported by the head script and the
+/// render scripts, to the cache directory. Returns its absolute path.
+pub(crate) fn write_head_tags(cache_dir: &mut CacheDir) -> Result<PathBuf> {
+    Ok(cache_dir
+        .insert("head_tags.js", HEAD_TAGS_SCRIPT.as_bytes())?
+        .canonicalize()?)
+}
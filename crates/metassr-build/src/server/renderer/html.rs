@@ -7,23 +7,36 @@ use html_generator::{
     template::HtmlTemplate,
 };
 use metassr_fs_analyzer::dist_dir::PageEntry;
+use serde_json::Value;
 
 pub struct HtmlRenderer<'a> {
+    template: &'a HtmlTemplate,
     head: String,
     body: String,
+    props: Value,
     page_entry: &'a PageEntry,
 }
 
 impl<'a> HtmlRenderer<'a> {
-    pub fn new(head: &str, body: &str, page_entry: &'a PageEntry) -> Self {
+    /// `props` must be the same props the body was rendered with; they are embedded in the page for hydration.
+    pub fn new(
+        template: &'a HtmlTemplate,
+        head: &str,
+        body: &str,
+        props: &Value,
+        page_entry: &'a PageEntry,
+    ) -> Self {
         Self {
+            template,
             head: head.to_string(),
             body: body.to_string(),
+            props: props.clone(),
             page_entry,
         }
     }
 
     pub fn render(&self) -> Result<HtmlOutput> {
+        // In dependency order: the shared chunks run before the page's own script.
         let scripts: Vec<String> = self
             .page_entry
             .scripts
@@ -38,14 +51,31 @@ impl<'a> HtmlRenderer<'a> {
             .map(|p| Path::new("/").join(p).to_str().unwrap().to_owned())
             .collect();
 
+        // The chunks loaded on demand and the fonts are only discovered once the scripts run
+        // and the stylesheets are parsed, so they're preloaded. The scripts are preloaded by
+        // the HTML builder.
+        let fonts = self.page_entry.assets.iter().filter(|p| {
+            p.extension()
+                .is_some_and(|ext| ext == "woff" || ext == "woff2")
+        });
+        let preloads: Vec<String> = self
+            .page_entry
+            .chunks
+            .iter()
+            .chain(fonts)
+            .map(|p| Path::new("/").join(p).to_str().unwrap().to_owned())
+            .collect();
+
         let html_props = HtmlProps::new()
             .head(&self.head)
             .body(&format!("<div id='root'>{}</div>", self.body))
             .lang("en")
+            .preloads(preloads)
             .scripts(scripts)
-            .styles(styles);
+            .styles(styles)
+            .data(self.props.clone());
 
-        let builder = HtmlBuilder::new(HtmlTemplate::default(), html_props.build());
+        let builder = HtmlBuilder::new(self.template.clone(), html_props.build());
 
         Ok(builder.generate())
     }
@@ -1,3 +1,4 @@
+pub mod document;
 pub mod head;
 pub mod html;
 pub mod page;
\ No newline at end of file
@@ -2,19 +2,24 @@ use std::ffi::OsStr;
 
 use anyhow::Result;
 
+use html_generator::template::HtmlTemplate;
+
 use metassr_fs_analyzer::dist_dir::PageEntry;
 use metassr_utils::cache_dir::CacheDir;
+use serde_json::{Map, Value};
 
 use crate::{
     server::{manifest::Manifest, render_exec::RenderExec},
     traits::Exec,
 };
 
-use super::{head::HeadRenderer, html::HtmlRenderer};
+use super::{document::load_template, head::HeadRenderer, html::HtmlRenderer};
 
 pub struct PageRenderer {
+    template: HtmlTemplate,
     head: String,
     body: String,
+    props: Value,
     entries: PageEntry,
 }
 
@@ -28,19 +33,33 @@ impl PageRenderer {
         let cache = CacheDir::new(&manifest.global.cache)?;
         let entry = manifest.get(route).unwrap().clone();
 
-        let exec = RenderExec::new(entry.id, &entry.renderer)?;
-        let body = exec.exec()?;
-        let head = HeadRenderer::new(&manifest.global.head, cache).render(false)?;
+        let props = Value::Object(Map::new());
+        let params = Value::Object(Map::new());
+        let output = RenderExec::new(entry.id, &entry.renderer, &props, &params)?.exec()?;
+        let body = output.body;
+        let head =
+            HeadRenderer::new(&manifest.global.head, cache).render(false, Some(&output.head))?;
+        let template = load_template(manifest.global.document.as_deref())?;
 
         Ok(Self {
+            template,
             head,
             body,
+            props,
             entries: entry.page_entry,
         })
     }
     pub fn render(&self) -> Result<String> {
-        Ok(HtmlRenderer::new(&self.head, &self.body, &self.entries)
+        Ok(
+            HtmlRenderer::new(
+                &self.template,
+                &self.head,
+                &self.body,
+                &self.props,
+                &self.entries,
+            )
             .render()?
-            .to_string())
+            .to_string(),
+        )
     }
 }
@@ -5,12 +5,12 @@ use std::{
 
 use anyhow::Result;
 
-use metassr_fs_analyzer::src_dir::PagesEntriesType;
+use metassr_fs_analyzer::src_dir::SourceDirContainer;
 use metassr_utils::cache_dir::CacheDir;
 
 use crate::{traits::Generate, utils::setup_page_path};
 
-use super::render::ServerRender;
+use super::{render::ServerRender, renderer::head::write_head_tags};
 
 #[derive(Debug, Clone)]
 pub struct Targets(HashMap<PathBuf, i64>);
@@ -66,18 +66,45 @@ impl Default for Targets {
 
 pub struct TargetsGenerator<'a> {
     app: PathBuf,
-    pages: PagesEntriesType,
+    src: SourceDirContainer,
     cache: &'a mut CacheDir,
+    root: PathBuf,
+    revision: u64,
 }
 
 impl<'a> TargetsGenerator<'a> {
-    pub fn new(app: PathBuf, pages: PagesEntriesType, cache: &'a mut CacheDir) -> Self {
-        Self { app, pages, cache }
+    pub fn new(app: PathBuf, src: SourceDirContainer, cache: &'a mut CacheDir) -> Self {
+        Self {
+            app,
+            src,
+            cache,
+            root: PathBuf::new(),
+            revision: 0,
+        }
+    }
+
+    /// Sets the project root, which the IDs of the render functions don't depend on.
+    pub fn root(mut self, root: &Path) -> Self {
+        self.root = root.to_path_buf();
+        self
     }
+
+    /// Mixes `revision` into the IDs of the render functions.
+    pub fn revision(mut self, revision: u64) -> Self {
+        self.revision = revision;
+        self
+    }
+
     pub fn generate(&mut self) -> Result<Targets> {
         let mut targets = Targets::new();
-        for (page, page_path) in self.pages.iter() {
-            let (func_id, render_script) = ServerRender::new(&self.app, page_path).generate()?;
+        let head_tags = write_head_tags(self.cache)?;
+        for (page, page_path) in self.src.pages.iter() {
+            let layouts = self.src.layouts_for(page);
+            let (func_id, render_script) = ServerRender::new(page, &self.app, page_path, &layouts)
+                .head_tags(&head_tags)
+                .root(&self.root)
+                .revision(self.revision)
+                .generate()?;
 
             let page = setup_page_path(page, "server.js");
             let path = self.cache.insert(
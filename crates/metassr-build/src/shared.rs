@@ -2,3 +2,8 @@ pub const APP_PATH_TAG: &str = "%APP_PATH%";
 pub const PAGE_PATH_TAG: &str = "%PAGE_PATH%";
 pub const ROOT_ID_TAG: &str = "%ROOT_ID%";
 pub const FUNC_ID_TAG: &str = "%FUNC_ID%";
+pub const HEAD_PATH_TAG: &str = "%HEAD_PATH%";
+pub const HEAD_TAGS_PATH_TAG: &str = "%HEAD_TAGS_PATH%";
+pub const LAYOUTS_IMPORTS_TAG: &str = "%LAYOUTS_IMPORTS%";
+pub const LAYOUTS_TAG: &str = "%LAYOUTS%";
+pub const LAYOUT_MODULES_TAG: &str = "%LAYOUT_MODULES%";
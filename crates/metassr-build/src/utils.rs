@@ -1,8 +1,18 @@
+use anyhow::Result;
 use std::{
     ffi::OsStr,
     path::{Path, PathBuf},
 };
 
+/// Creates an empty directory with a random name in the temporary directory of the system,
+/// for the tests working on files.
+#[cfg(test)]
+pub fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(metassr_utils::rand::Rand::new().val().to_string());
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
 pub fn setup_page_path(page: &str, ext: &str) -> PathBuf {
     match Path::new(page) {
         path if path.file_stem() != Some(OsStr::new("index")) => {
@@ -12,3 +22,37 @@ pub fn setup_page_path(page: &str, ext: &str) -> PathBuf {
         path => path.to_path_buf().with_extension(ext),
     }
 }
+
+/// Generates the import statements and the list of the layouts wrapping a page, to fill
+/// `%LAYOUTS_IMPORTS%` and `%LAYOUTS%` in the render and hydrate scripts.
+///
+/// Layouts are expected from the outermost to the innermost. Each one is imported as
+/// `Layout{i}`, and its module as `Layout{i}Module` for its other exports (see
+/// [`layout_modules`]).
+pub fn setup_layouts(layouts: &[PathBuf], keep_extension: bool) -> Result<(String, String)> {
+    let mut imports = vec![];
+    let mut names = vec![];
+
+    for (i, layout) in layouts.iter().enumerate() {
+        let mut path = layout.canonicalize()?;
+        if !keep_extension {
+            path.set_extension("");
+        }
+
+        imports.push(format!(
+            "import Layout{i}, * as Layout{i}Module from \"{}\"",
+            path.display()
+        ));
+        names.push(format!("Layout{i}"));
+    }
+    Ok((imports.join("\n"), names.join(", ")))
+}
+
+/// Generates the list of the modules of `count` layouts imported by [`setup_layouts`], to
+/// fill `%LAYOUT_MODULES%` in the render scripts.
+pub fn layout_modules(count: usize) -> String {
+    (0..count)
+        .map(|i| format!("Layout{i}Module"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
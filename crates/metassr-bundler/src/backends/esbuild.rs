@@ -0,0 +1,34 @@
+use super::{BundleFuture, Bundler, Script};
+use crate::Compilation;
+use anyhow::Result;
+use lazy_static::lazy_static;
+use metassr_utils::checker::CheckerState;
+use std::sync::Mutex;
+
+/// The bundling script, which runs the compilations through esbuild.
+static ESBUILD_SCRIPT: &str = include_str!("../esbuild.js");
+
+lazy_static! {
+    /// A detector for if the bundling script `./esbuild.js` is loaded or not.
+    static ref IS_ESBUILD_SCRIPT_LOADED: Mutex<CheckerState> = Mutex::new(CheckerState::default());
+}
+
+/// A faster backend, running the compilations through esbuild. The project needs `esbuild`
+/// among its dependencies.
+///
+/// It trades features for speed: each page is bundled on its own, without shared chunks nor
+/// code splitting (dynamically imported modules are bundled into the page), and nothing is
+/// cached between builds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Esbuild;
+
+impl Bundler for Esbuild {
+    fn bundle(&self, compilations: &[Compilation]) -> Result<BundleFuture> {
+        Script {
+            source: ESBUILD_SCRIPT,
+            function: "esbuild_bundling",
+            loaded: &IS_ESBUILD_SCRIPT_LOADED,
+        }
+        .run(compilations)
+    }
+}
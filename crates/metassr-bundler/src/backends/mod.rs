@@ -0,0 +1,301 @@
+pub mod esbuild;
+pub mod rspack;
+
+pub use esbuild::Esbuild;
+pub use rspack::Rspack;
+
+use crate::{
+    diagnostics::{format_group, group_by_file, BundlingError, Diagnostic, Diagnostics},
+    BundleOutput, Compilation,
+};
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use metacall::{loaders, metacall, MetacallFuture, MetacallValue};
+use metassr_utils::checker::CheckerState;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+use tracing::{error, warn};
+
+/// The future of a bundling run, resolving with what each of its compilations produced, by name.
+pub type BundleFuture = Pin<Box<dyn Future<Output = Result<HashMap<String, BundleOutput>>> + Send>>;
+
+/// A bundler backend: it bundles the entries of each compilation into the compilation's output
+/// directory, for the compilation's profile, and reports the files it emitted.
+///
+/// [`WebBundler`](crate::WebBundler) and [`MultiBundler`](crate::MultiBundler) run their
+/// compilations through the backend selected by their [`BundleOptions`](crate::options::BundleOptions).
+pub trait Bundler: Debug + Send + Sync {
+    /// Starts bundling `compilations` at once, returning the future of the run.
+    ///
+    /// # Errors
+    ///
+    /// The run fails if the backend cannot be started, and its future fails if bundling fails.
+    /// When the backend reports errors, the future's `Err` is a [`BundlingError`] holding the
+    /// errors of every compilation.
+    fn bundle(&self, compilations: &[Compilation]) -> Result<BundleFuture>;
+}
+
+lazy_static! {
+    /// The bundling runs in progress, by call ID. The ID is passed to the callbacks of the
+    /// bundling functions, so each of them completes its own run.
+    static ref PENDING_BUNDLINGS: Mutex<HashMap<i64, Arc<CompilationWait>>> = Mutex::new(HashMap::new());
+}
+/// The ID of the next bundling run.
+static NEXT_CALL_ID: AtomicI64 = AtomicI64::new(0);
+
+/// A Node script bundling compilations through one of its functions, called via MetaCall.
+///
+/// The function takes the compilations as a JSON array, and resolves with the report of each
+/// compilation (its diagnostics, time and entrypoints) as a JSON object by name.
+struct Script {
+    /// The source of the script.
+    source: &'static str,
+    /// The name of the bundling function exported by the script.
+    function: &'static str,
+    /// A detector for if the script is loaded or not. It is used to solve multiple loading script error in metacall.
+    loaded: &'static Mutex<CheckerState>,
+}
+
+impl Script {
+    /// Starts running `compilations` through the bundling function of the script, returning
+    /// the future of the run.
+    fn run(&self, compilations: &[Compilation]) -> Result<BundleFuture> {
+        // Lock the mutex to check if the bundling script is already loaded
+        let mut guard = self.loaded.lock().unwrap();
+        if !guard.is_true() {
+            // If not loaded, attempt to load the script into MetaCall
+            if let Err(e) = loaders::from_memory("node", self.source) {
+                return Err(anyhow!("Cannot load bundling script: {e:?}"));
+            }
+            // Mark the script as loaded
+            guard.make_true();
+        }
+        // Drop the lock on the mutex as it's no longer needed
+        drop(guard);
+
+        // Completes the run whose ID the callback got, with what the bundling function settled with
+        fn complete(call: Box<dyn MetacallValue>, result: Settlement) {
+            settle(
+                &mut PENDING_BUNDLINGS.lock().unwrap(),
+                call.downcast::<i64>().ok(),
+                result,
+            );
+        }
+
+        // Resolve callback when the bundling process is completed, with its reports (diagnostics and emitted files)
+        fn resolve(reports: Box<dyn MetacallValue>, call: Box<dyn MetacallValue>) {
+            complete(call, Ok(reports.downcast::<String>().ok()));
+        }
+
+        // Reject callback for handling errors during the bundling process
+        fn reject(err: Box<dyn MetacallValue>, call: Box<dyn MetacallValue>) {
+            let message = match err.downcast::<String>() {
+                Ok(message) => message,
+                Err(err) => format!("{err:?}"),
+            };
+            complete(call, Err(message));
+        }
+
+        // Register the run before calling the function, so its callbacks always find it
+        let call = NEXT_CALL_ID.fetch_add(1, Ordering::Relaxed);
+        let compilation_wait = Arc::new(CompilationWait::default());
+        PENDING_BUNDLINGS
+            .lock()
+            .unwrap()
+            .insert(call, Arc::clone(&compilation_wait));
+
+        // Call the bundling function in the MetaCall script with the serialized compilations
+        let future =
+            match metacall::<MetacallFuture>(self.function, [serde_json::to_string(compilations)?])
+            {
+                Ok(future) => future,
+                Err(e) => {
+                    PENDING_BUNDLINGS.lock().unwrap().remove(&call);
+                    return Err(anyhow!("Cannot call the bundling function: {e:?}"));
+                }
+            };
+
+        // Set the resolve and reject handlers for the bundling future, along with the run's ID
+        future.then(resolve).catch(reject).data(call).await_fut();
+
+        let bundling = Bundling(compilation_wait);
+        Ok(Box::pin(async move { finish(bundling.await) }))
+    }
+}
+
+/// Completes the run `call` of the `pending` runs with `result`. The ID is passed to every
+/// callback along with the run, so a callback without it is a bug: it is logged, and the
+/// pending runs, which may still settle, are left alone.
+fn settle(pending: &mut HashMap<i64, Arc<CompilationWait>>, call: Option<i64>, result: Settlement) {
+    match call {
+        Some(call) => {
+            if let Some(compilation_wait) = pending.remove(&call) {
+                compilation_wait.complete(result);
+            }
+        }
+        None => error!(
+            target = "bundler",
+            message = format!("A bundling function settled without the ID of its run: {result:?}")
+        ),
+    }
+}
+
+/// What a bundling function settled with: the reports it resolved with as JSON (`None` if it
+/// resolved with something else), or the message it was rejected with.
+type Settlement = std::result::Result<Option<String>, String>;
+
+/// The completion of a bundling run: set by the callbacks of the bundling function, and
+/// awaited by the caller of the run.
+#[derive(Default)]
+struct CompilationWait {
+    outcome: Mutex<Outcome>,
+}
+
+#[derive(Default)]
+struct Outcome {
+    /// What the bundling function settled with.
+    result: Option<Settlement>,
+    /// The task awaiting the run, woken up once it completes.
+    waker: Option<Waker>,
+}
+
+impl CompilationWait {
+    fn complete(&self, result: Settlement) {
+        let mut outcome = self.outcome.lock().unwrap();
+        outcome.result = Some(result);
+        if let Some(waker) = outcome.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A future resolved once a bundling run completes.
+struct Bundling(Arc<CompilationWait>);
+
+impl Future for Bundling {
+    type Output = Settlement;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut outcome = self.0.outcome.lock().unwrap();
+        match outcome.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                outcome.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// What a bundling function resolves with, for each compilation.
+#[derive(Debug, Default, Deserialize)]
+struct BundlingReport {
+    #[serde(flatten)]
+    diagnostics: Diagnostics,
+    #[serde(flatten)]
+    output: BundleOutput,
+}
+
+/// Collects what a bundling run settled with: the output of each compilation, or the
+/// errors of all of them. A run without valid reports fails, as its outputs are unknown.
+fn finish(outcome: Settlement) -> Result<HashMap<String, BundleOutput>> {
+    let reports: BTreeMap<String, BundlingReport> = match outcome {
+        Ok(Some(reports)) => match serde_json::from_str(&reports) {
+            Ok(reports) => reports,
+            Err(e) => {
+                let message = format!("The bundler returned an invalid report: {e}");
+                return Err(BundlingError {
+                    errors: vec![Diagnostic::new(&message)],
+                }
+                .into());
+            }
+        },
+        Ok(None) => {
+            return Err(BundlingError {
+                errors: vec![Diagnostic::new("The bundler returned no report")],
+            }
+            .into());
+        }
+        Err(message) => {
+            error!("Bundling rejected: {message}");
+            return Err(anyhow!("Bundling failed:\n{message}"));
+        }
+    };
+
+    let mut errors = vec![];
+    let mut outputs = HashMap::new();
+    for (name, report) in reports {
+        let BundlingReport {
+            diagnostics,
+            mut output,
+        } = report;
+        for (file, warnings) in group_by_file(&diagnostics.warnings) {
+            warn!(
+                target = "bundler",
+                message = format!("Warning in {}", format_group(&file, &warnings))
+            );
+        }
+        errors.extend(diagnostics.errors);
+        output.warnings = diagnostics.warnings;
+        outputs.insert(name, output);
+    }
+
+    if !errors.is_empty() {
+        return Err(BundlingError { errors }.into());
+    }
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_or_invalid_reports_fail() {
+        for outcome in [Ok(None), Ok(Some("not json".to_owned()))] {
+            let err = finish(outcome).unwrap_err();
+            let err = err.downcast_ref::<BundlingError>().unwrap();
+            assert_eq!(err.errors.len(), 1);
+        }
+
+        let outputs = finish(Ok(Some(
+            r#"{"client":{"errors":[],"warnings":[],"entrypoints":{}}}"#.to_owned(),
+        )))
+        .unwrap();
+        assert!(outputs.contains_key("client"));
+    }
+
+    #[test]
+    fn runs_settled_without_id_stay_pending() {
+        let (first, second) = (
+            Arc::new(CompilationWait::default()),
+            Arc::new(CompilationWait::default()),
+        );
+        let mut pending = HashMap::from([(0, Arc::clone(&first)), (1, Arc::clone(&second))]);
+
+        settle(&mut pending, Some(0), Ok(None));
+        assert_eq!(first.outcome.lock().unwrap().result, Some(Ok(None)));
+        assert_eq!(second.outcome.lock().unwrap().result, None);
+
+        settle(&mut pending, None, Ok(None));
+        assert_eq!(pending.len(), 1);
+        assert_eq!(second.outcome.lock().unwrap().result, None);
+
+        settle(&mut pending, Some(1), Err("failed".to_owned()));
+        assert!(pending.is_empty());
+        assert!(matches!(
+            second.outcome.lock().unwrap().result,
+            Some(Err(_))
+        ));
+    }
+}
@@ -0,0 +1,32 @@
+use super::{BundleFuture, Bundler, Script};
+use crate::Compilation;
+use anyhow::Result;
+use lazy_static::lazy_static;
+use metassr_utils::checker::CheckerState;
+use std::sync::Mutex;
+
+/// The bundling script, which runs the compilations through rspack.
+pub(crate) static BUILD_SCRIPT: &str = include_str!("../bundle.js");
+
+lazy_static! {
+    /// A detector for if the bundling script `./bundle.js` is loaded or not.
+    static ref IS_BUNDLING_SCRIPT_LOADED: Mutex<CheckerState> = Mutex::new(CheckerState::default());
+}
+
+/// The default backend, running the compilations through rspack in a single multi-compiler run.
+///
+/// Client bundles get shared `vendor` and `common` chunks and are split on dynamic `import()`,
+/// and modules are cached between builds in the compilations' cache directories.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rspack;
+
+impl Bundler for Rspack {
+    fn bundle(&self, compilations: &[Compilation]) -> Result<BundleFuture> {
+        Script {
+            source: BUILD_SCRIPT,
+            function: "web_bundling",
+            loaded: &IS_BUNDLING_SCRIPT_LOADED,
+        }
+        .run(compilations)
+    }
+}
@@ -1,6 +1,11 @@
-const { rspack } = require('@rspack/core');
+const { rspack, DefinePlugin } = require('@rspack/core');
+const fs = require('fs');
 const path = require('path');
 
+// Map stack traces of the bundled scripts back to their sources, so render errors point to
+// the project's files.
+process.setSourceMapsEnabled?.(true);
+
 /**
  * Safely parses a JSON string, returning undefined if parsing fails.
  * @param {string} json - The JSON string to parse.
@@ -17,18 +22,11 @@ function safelyParseJSON(json) {
 // Default configuration object for rspack bundling process
 let config = {
     output: {
-        filename: '[name].js', // Output filename with the entry name
-        library: {
-            type: 'commonjs2', // Set library type to CommonJS2 (Node.js modules)
-        },
-        publicPath: '' // Specify the base path for all assets within the application
+        devtoolModuleFilenameTemplate: '[absolute-resource-path]', // Use absolute source paths in source maps
     },
     resolve: {
         extensions: ['.js', '.jsx', '.tsx', '.ts'] // Extensions that will be resolved
     },
-    optimization: {
-        minimize: false, // Disable minimization for easier debugging
-    },
     module: {
         rules: [
             {
@@ -80,52 +78,553 @@ let config = {
                 },
                 type: 'javascript/auto', // Specify the type as auto
             },
-            {
-                test: /\.(png|svg|jpg)$/, // Rule for image files (PNG, SVG, JPG)
-                type: 'asset/inline', // Inline assets as Base64 strings
-            },
         ],
     },
 };
 
+// Assets up to this size (in bytes) are inlined as data URLs, saving a request for each; larger
+// ones are emitted as files.
+const INLINE_LIMIT = 4 * 1024;
+
+/**
+ * Returns the rule for image and font files. Those above `INLINE_LIMIT` are emitted as files
+ * under `assets/` and referenced by URL, the others are inlined. Both profiles name the files
+ * the same way, so server-rendered and hydrated pages agree on URLs.
+ * @param {boolean} emit - Whether the files are written; only the client build writes them.
+ * @returns {Object} - The rspack rule.
+ */
+function assetsRule(emit) {
+    return {
+        test: /\.(png|jpe?g|gif|webp|avif|svg|woff2?)$/i, // Images and fonts
+        type: 'asset',
+        parser: {
+            dataUrlCondition: { maxSize: INLINE_LIMIT },
+        },
+        generator: {
+            filename: 'assets/[name].[contenthash:8][ext]',
+            emit,
+        },
+    };
+}
+
+// PostCSS configuration files, looked up in the project root (see `STYLE_CONFIGS` in lib.rs).
+const POSTCSS_CONFIGS = [
+    'postcss.config.js', 'postcss.config.cjs', 'postcss.config.mjs', 'postcss.config.json',
+    '.postcssrc', '.postcssrc.json', '.postcssrc.js', '.postcssrc.cjs',
+];
+
+/**
+ * Returns the rules of stylesheets: CSS and Sass (`.scss` and `.sass`, compiled by the
+ * project's `sass-loader`), both treated as CSS Modules when named `*.module.*`. If the
+ * project has a PostCSS configuration (e.g. for Tailwind), they go through `postcss-loader`.
+ * @returns {Array<Object>} - The rspack rules.
+ */
+function stylesRules() {
+    const postcss = POSTCSS_CONFIGS.some((file) => fs.existsSync(path.join(process.cwd(), file)))
+        ? ['postcss-loader']
+        : [];
+    return [
+        { test: /\.css$/i, use: postcss, type: 'css/auto' },
+        // Loaders run from last to first: Sass is compiled to CSS before PostCSS runs
+        { test: /\.s[ac]ss$/i, use: [...postcss, 'sass-loader'], type: 'css/auto' },
+    ];
+}
+
+/**
+ * Returns the parser and generator options of stylesheets. The class names of CSS Modules are
+ * derived from the module's path and the class name only, so server-rendered and hydrated
+ * pages agree on them.
+ * @param {boolean} emit - Whether the stylesheets are emitted; the server bundles only get the class names of CSS Modules.
+ * @returns {Object} - The `parser` and `generator` options of rspack modules.
+ */
+function stylesOptions(emit) {
+    return {
+        parser: {
+            'css/auto': { namedExports: false }, // Class names are imported as `import styles from './page.module.css'`
+        },
+        generator: {
+            'css/auto': { exportsOnly: !emit, localIdentName: '[local]-[hash]' },
+        },
+    };
+}
+
+// The Node version running MetaSSR, which runs the server bundles too.
+const NODE_VERSION = process.versions.node;
+
+/**
+ * An error in the project's setup rather than in its bundler configuration, such as a missing
+ * dependency. It's reported with the file to fix.
+ */
+class SetupError extends Error {
+    /**
+     * @param {string} message - What's wrong, and how to fix it.
+     * @param {string} file - The project's file to fix, relative to the project root.
+     */
+    constructor(message, file) {
+        super(message);
+        this.file = file;
+    }
+}
+
+/**
+ * Returns the version of `core-js` installed in the project, which the injected polyfills are
+ * imported from. swc only injects the polyfills this version has.
+ * @returns {string} - The version.
+ * @throws {SetupError} - If `core-js` isn't installed, as the polyfills couldn't be imported.
+ */
+function coreJsVersion() {
+    try {
+        return require(require.resolve('core-js/package.json', { paths: [process.cwd()] })).version;
+    } catch (_) {
+        throw new SetupError(
+            'Polyfills are enabled, but `core-js` is not installed. Add it to the dependencies of the project (`npm install core-js`), or disable `polyfills`.',
+            'package.json',
+        );
+    }
+}
+
+/**
+ * Returns the swc `env` options of a profile. Client bundles are lowered to the syntax of the
+ * targeted browsers and, with polyfills, import the core-js modules of the features they use
+ * and the browsers lack. Server bundles are lowered to the syntax of the Node version running
+ * MetaSSR.
+ * @param {string} profile - The bundling profile, `client` or `server`.
+ * @param {Object} targets - The targeted browsers (`browsers`, browserslist queries) and whether they get `polyfills`.
+ * @returns {Object|undefined} - The `env` options, undefined to leave the syntax as is.
+ */
+function swcEnv(profile, { browsers = [], polyfills = false }) {
+    if (profile === 'server') {
+        return { targets: { node: NODE_VERSION } };
+    }
+    if (!browsers.length) {
+        return undefined;
+    }
+    return polyfills
+        ? { targets: browsers, mode: 'usage', coreJs: coreJsVersion() }
+        : { targets: browsers };
+}
+
+/**
+ * Returns the dependencies of a project's `package.json`. They are installed wherever the
+ * server runs, so the server bundle `require`s them instead of bundling them.
+ * @param {string} root - The project root.
+ * @returns {Array<string>} - The names of the dependencies.
+ */
+function projectDependencies(root) {
+    try {
+        const pkg = JSON.parse(fs.readFileSync(path.join(path.resolve(root), 'package.json'), 'utf8'));
+        return Object.keys(pkg.dependencies ?? {});
+    } catch (_) {
+        return [];
+    }
+}
+
+// Bundling profiles: scripts hydrated in the browser, and scripts rendered on the server by Node.
+// Each one is configured from the compilation's browser `targets` and `externals` root.
+const profiles = {
+    client: ({ targets: { browsers = [] } }) => ({
+        // Browser code, whose runtime is generated for the targeted browsers if there are any
+        target: browsers.length ? ['web', `browserslist:${browsers.join(', ')}`] : 'web',
+        // Content-hashed filenames, so browsers can cache them forever
+        filename: '[name].[contenthash:8].js',
+        cssFilename: '[name].[contenthash:8].css',
+        // Shared and dynamically imported chunks
+        chunkFilename: 'chunks/[name].[contenthash:8].js',
+        cssChunkFilename: 'chunks/[name].[contenthash:8].css',
+        rules: [assetsRule(true), ...stylesRules()],
+        styles: stylesOptions(true),
+        splitChunks: {
+            chunks: 'all',
+            cacheGroups: {
+                // Dependencies change less often than pages, so they're cached apart from them
+                vendor: {
+                    test: /[\\/]node_modules[\\/]/,
+                    name: 'vendor',
+                    priority: 10,
+                },
+                // Modules imported by several pages (e.g. `_app` and layouts)
+                common: {
+                    minChunks: 2,
+                    name: 'common',
+                    priority: 5,
+                    reuseExistingChunk: true,
+                },
+            },
+        },
+    }),
+    server: ({ externals: root }) => {
+        const dependencies = root ? projectDependencies(root) : [];
+        return {
+            // Node built-ins are required as is, and browser polyfills are left out
+            target: `node${NODE_VERSION.split('.').slice(0, 2).join('.')}`,
+            // Stable filenames, the manifest points to the render scripts directly
+            filename: '[name].js',
+            cssFilename: '[name].css',
+            library: { type: 'commonjs2' }, // Render functions are exported to be called via metacall
+            rules: [assetsRule(false), ...stylesRules()],
+            styles: stylesOptions(false),
+            splitChunks: false, // Each render script is loaded on its own
+            // Keep the project's dependencies (and their subpaths, e.g. `react-dom/server`) external
+            externals: root ? [({ request }, callback) =>
+                dependencies.some((dep) => request === dep || request.startsWith(`${dep}/`))
+                    ? callback(null, `commonjs ${request}`)
+                    : callback()
+            ] : undefined,
+        };
+    },
+};
+
+// Build modes: development builds are fast and debuggable, production builds are small.
+const modes = {
+    development: {
+        minimize: false, // Keep the bundles readable
+        treeShaking: false, // Skip the analysis of unused exports and side effects, to build faster
+    },
+    production: {
+        minimize: true, // Minify JS (SWC) and CSS (Lightning CSS)
+        treeShaking: true, // Drop unused exports and side-effect-free modules
+    },
+};
+
+// Source map styles, as rspack `devtool` values.
+const devtools = {
+    none: false,
+    hidden: 'hidden-source-map', // Emitted but not referenced by the bundles, e.g. for error trackers
+    full: 'source-map',
+};
+
+/**
+ * Converts rspack stats errors or warnings to plain diagnostics.
+ * @param {Array} items - The `errors` or `warnings` of the JSON stats.
+ * @returns {Array} - Diagnostics with their module, file, line, column and message.
+ */
+function toDiagnostics(items = []) {
+    return items.map((item) => {
+        // `loc` looks like `12:5`, `12:5-9` or `12-14`.
+        const [, line, column] = /^(\d+)(?::(\d+))?/.exec(item.loc ?? '') ?? [];
+        // Loaders prefix the module identifier, e.g. `builtin:swc-loader??ruleSet[1]!/app/src/index.jsx`.
+        const resource = item.moduleIdentifier?.split('!').pop();
+
+        return {
+            module: item.moduleName ?? null,
+            file: resource && path.isAbsolute(resource) ? resource : null,
+            line: line ? Number(line) : null,
+            column: column ? Number(column) : null,
+            message: item.message,
+        };
+    });
+}
+
 /**
- * Bundles web resources using rspack.
- * @param {Object|string} entry - The entry point(s) for the bundling process (can be a string or JSON object).
- * @param {string} dist - The distribution path where bundled files will be output.
- * @returns {Promise} - Resolves when bundling is successful, rejects if there is an error.
- */
-async function web_bundling(entry, dist) {
-    // Create a bundler instance using the config and parameters
-    const compiler = rspack(
-        {
-            ...config, // Merge with the default config
-            entry: safelyParseJSON(entry) ?? entry, // Parse entry if it's JSON, otherwise use it as is
-            output: dist ? {
-                ...config.output,
-                path: path.join(process.cwd(), dist), // Use current working directory and output path
-            } : config.output,
-            // minimize: true,
-            name: 'Client', // Name of the bundle (Client)
-            mode: 'production', // Set mode to development (for non-minimized builds)
-            devtool: 'source-map', // Enable source maps for better debugging
-            stats: { preset: 'errors-warnings', timings: true, colors: true }, // Customize bundling stats output
-            target: 'web', // Set the target environment to web (for browser usage)
+ * Returns the chunks an entrypoint loads: its initial chunks, and the chunks it loads on demand
+ * through dynamic `import()`, including the chunks those import in turn.
+ * @param {Object} entrypoint - The entrypoint of the JSON stats.
+ * @param {Map} chunks - The chunks of the JSON stats, by id.
+ * @returns {Array<Object>} - The chunks of the entrypoint.
+ */
+function entryChunks(entrypoint, chunks) {
+    const found = [];
+    const seen = new Set();
+    const queue = [...(entrypoint.chunks ?? [])];
+    while (queue.length) {
+        const id = queue.shift();
+        const chunk = chunks.get(id);
+        if (seen.has(id) || !chunk) {
+            continue;
         }
-    );
+        seen.add(id);
+        found.push(chunk);
+        queue.push(...(chunk.children ?? []));
+    }
+    return found;
+}
+
+/**
+ * Returns the project's source files among bundled modules, leaving out dependencies.
+ * @param {Array} modules - The modules of a chunk in the JSON stats.
+ * @returns {Array<string>} - The absolute paths of the files.
+ */
+function sourceFiles(modules = []) {
+    return modules.flatMap((module) => {
+        // Concatenated modules list the modules they're made of
+        if (module.modules) {
+            return sourceFiles(module.modules);
+        }
+        const resource = module.nameForCondition ?? module.identifier?.split('!').pop();
+        return resource && path.isAbsolute(resource) && !resource.includes(`${path.sep}node_modules${path.sep}`)
+            ? [resource]
+            : [];
+    });
+}
+
+/**
+ * Creates the rspack configuration of a compilation.
+ * @param {Object} compilation - The compilation to configure.
+ * @param {string} compilation.name - The name of the compilation, unique within a bundling run.
+ * @param {Object} compilation.entry - The entry points of the compilation, by name.
+ * @param {string} compilation.dist - The distribution path where bundled files will be output.
+ * @param {string} compilation.profile - The bundling profile, `client` or `server`.
+ * @param {string} compilation.mode - The build mode, `production` or `development`.
+ * @param {string} compilation.sourceMaps - The source maps of the client bundles, `none`, `hidden` or `full`.
+ * @param {Object|null} compilation.cache - The persistent cache settings (`directory` and `version`), or null to disable it.
+ * @param {Object} compilation.alias - The absolute paths module requests are resolved to, by request.
+ * @param {Object} compilation.define - The code replacing expressions at build time, by expression.
+ * @param {Object} compilation.targets - The browsers client bundles are built for (`browsers`) and whether they get `polyfills`.
+ * @param {string} compilation.publicPath - The URL the emitted files are served under, e.g. `/dist/`.
+ * @param {string|null} compilation.externals - The project root whose dependencies server bundles leave external, or null to bundle them.
+ * @param {string} compilation.configHook - The configuration hook customizing the configuration (see `createConfigs`).
+ * @param {string|null} compilation.context - The directory module IDs and source map paths are relative to, or null for the current directory.
+ * @returns {Object} - The rspack configuration.
+ */
+function createConfig({
+    name, entry, dist, profile, mode = 'production', sourceMaps = 'hidden', cache = null, alias = {},
+    define = {}, targets = {}, publicPath = '/', externals: root = null, context = null,
+}) {
+    const {
+        target, filename, cssFilename, chunkFilename, cssChunkFilename, library, rules, styles,
+        splitChunks, externals,
+    } = profiles[profile]({ targets, externals: root });
+    // Each compilation gets its own copy, which `metassr.bundler.js` may change in place
+    const defaults = structuredClone(config);
+    const env = swcEnv(profile, targets);
+    for (const rule of defaults.module.rules) {
+        rule.use.options.env = env;
+    }
+    const output = {
+        ...defaults.output, publicPath, filename, cssFilename, chunkFilename, cssChunkFilename, library,
+    };
+    const { minimize, treeShaking } = modes[mode] ?? modes.production;
+    // Render errors are mapped back to the sources by Node, which needs the maps referenced
+    const devtool = profile === 'server' && sourceMaps !== 'none'
+        ? devtools.full
+        : devtools[sourceMaps] ?? devtools.hidden;
 
-    // Return a promise that runs the bundling process and resolves or rejects based on the result
+    return {
+        ...defaults, // Merge with the default config
+        // Module IDs are derived from paths relative to it, so bundles don't depend on where the project is
+        context: path.resolve(context ?? '.'),
+        entry,
+        resolve: { ...defaults.resolve, alias }, // Modules generated by the build, e.g. `metassr/image`
+        output: dist ? {
+            ...output,
+            path: path.join(process.cwd(), dist), // Use current working directory and output path
+        } : output,
+        module: {
+            ...defaults.module,
+            rules: [...defaults.module.rules, ...rules],
+            ...styles,
+        },
+        optimization: {
+            splitChunks,
+            minimize,
+            usedExports: treeShaking,
+            sideEffects: treeShaking,
+            innerGraph: treeShaking,
+            nodeEnv: mode, // Replaces `process.env.NODE_ENV`, so development-only code is dropped in production
+        },
+        externals,
+        // Public environment variables, e.g. `process.env.METASSR_PUBLIC_API_URL`
+        plugins: [new DefinePlugin(define)],
+        name, // Name of the compilation, used to report its results
+        mode: modes[mode] ? mode : 'production',
+        devtool,
+        cache: Boolean(cache),
+        experiments: {
+            css: true, // Native CSS support, with CSS Modules and extraction into `.css` files
+            // Modules are only rebuilt when they, or the cache version, changed since the last build
+            cache: cache ? {
+                type: 'persistent',
+                version: cache.version,
+                storage: {
+                    type: 'filesystem',
+                    directory: path.resolve(cache.directory),
+                },
+            } : undefined,
+        },
+        stats: { preset: 'errors-warnings', timings: true, colors: true }, // Customize bundling stats output
+        target,
+    };
+}
+
+/**
+ * Reports the results of a compilation: its errors and warnings as structured data, its
+ * duration, and the files emitted for each entry.
+ * @param {Object} stats - The stats of the compilation.
+ * @returns {Object} - The errors, warnings, time and entrypoints of the compilation.
+ */
+function report(stats) {
+    const { errors, warnings, entrypoints, chunks, time } = stats.toJson({
+        all: false,
+        timings: true,
+        errors: true,
+        warnings: true,
+        entrypoints: true,
+        chunks: true,
+        chunkRelations: true,
+        chunkModules: true,
+    });
+    const chunksById = new Map((chunks ?? []).map((chunk) => [chunk.id, chunk]));
+    return {
+        errors: toDiagnostics(errors),
+        warnings: toDiagnostics(warnings),
+        time: time ?? 0, // How long the compilation took, in milliseconds
+        entrypoints: Object.fromEntries(
+            Object.entries(entrypoints ?? {}).map(([name, entrypoint]) => {
+                const chunks = entryChunks(entrypoint, chunksById);
+                return [
+                    name,
+                    {
+                        // Ordered as they have to be loaded: shared chunks before the entry's own
+                        files: entrypoint.assets.map((asset) => asset.name ?? asset),
+                        chunks: chunks.filter((chunk) => !chunk.initial).flatMap((chunk) => chunk.files),
+                        // The asset files referenced by the files loaded with the entry
+                        assets: chunks
+                            .filter((chunk) => chunk.initial)
+                            .flatMap((chunk) => chunk.auxiliaryFiles ?? [])
+                            .filter((file) => !file.endsWith('.map')),
+                        // What the entry was built from, to know when it has to be rebuilt
+                        modules: [...new Set(chunks.flatMap((chunk) => sourceFiles(chunk.modules)))],
+                    },
+                ];
+            })
+        ),
+    };
+}
+
+// The project's configuration hook, in the project root.
+const CONFIG_FILE = 'metassr.bundler.js';
+
+/**
+ * The configuration hook of a compilation: the project's `metassr.bundler.js`, unless the
+ * compilation sets another one.
+ * @param {Object} compilation - The compilation (see `createConfig`).
+ * @returns {string} - The absolute path of the hook.
+ */
+function hookFile({ configHook }) {
+    return path.resolve(configHook ?? CONFIG_FILE);
+}
+
+/**
+ * Loads the function exported by a configuration hook, if there is one. It's loaded again on
+ * every bundling run, so its changes apply without a restart.
+ * @param {string} file - The absolute path of the hook.
+ * @returns {Function|null} - The function customizing the rspack configurations.
+ */
+function loadCustomizer(file) {
+    if (!fs.existsSync(file)) {
+        return null;
+    }
+
+    delete require.cache[require.resolve(file)];
+    const exported = require(file);
+    const customize = typeof exported === 'function' ? exported : exported?.default;
+    if (typeof customize !== 'function') {
+        throw new TypeError('it must export a function, `(config, { target, mode }) => config`');
+    }
+    return customize;
+}
+
+/**
+ * Converts an error thrown by a configuration hook to a diagnostic, located in the hook when
+ * the stack trace goes through it.
+ * @param {Error} error - The thrown error.
+ * @param {string} file - The absolute path of the hook.
+ * @returns {Object} - The diagnostic.
+ */
+function configDiagnostic(error, file) {
+    const escaped = file.replace(/[.*+?^${}()|[\]\\]/g, '\\$&');
+    const [, line, column] = new RegExp(`${escaped}:(\\d+)(?::(\\d+))?`).exec(error?.stack ?? '') ?? [];
+    return {
+        module: `./${path.relative(process.cwd(), file)}`,
+        file,
+        line: line ? Number(line) : null,
+        column: column ? Number(column) : null,
+        message: `Invalid bundler configuration: ${error?.message ?? error}`,
+    };
+}
+
+/**
+ * Creates the rspack configurations of the compilations, customized by their configuration
+ * hook, the project's `metassr.bundler.js` by default. It gets each default configuration with the compilation's context
+ * (`target`: `client` or `server`, `mode`, and the compilation's `name`), and returns the
+ * configuration to use; configurations changed in place can be left unreturned.
+ * @param {Array<Object>} compilations - The compilations (see `createConfig`).
+ * @returns {Promise<Array<Object>>} - The rspack configurations.
+ */
+async function createConfigs(compilations) {
+    const customizers = new Map();
+    for (const file of new Set(compilations.map(hookFile))) {
+        customizers.set(file, loadCustomizer(file));
+    }
+    return Promise.all(compilations.map(async (compilation) => {
+        const config = createConfig(compilation);
+        const customize = customizers.get(hookFile(compilation));
+        if (!customize) {
+            return config;
+        }
+
+        const context = { target: compilation.profile, mode: config.mode, name: compilation.name };
+        return (await customize(config, context)) ?? config;
+    }));
+}
+
+/**
+ * Reports a failed configuration as the error of the first compilation, so it's reported once.
+ * @param {Array<Object>} compilations - The compilations.
+ * @param {Error} error - The configuration error.
+ * @returns {string} - The report of each compilation as JSON, by name.
+ */
+function configFailure(compilations, error) {
+    const diagnostic = error instanceof SetupError
+        ? {
+            module: `./${error.file}`,
+            file: path.resolve(error.file),
+            line: null,
+            column: null,
+            message: error.message,
+        }
+        : configDiagnostic(error, hookFile(compilations[0]));
+    return JSON.stringify(Object.fromEntries(compilations.map(({ name }, i) => [name, {
+        errors: i === 0 ? [diagnostic] : [],
+        warnings: [],
+        time: 0,
+        entrypoints: {},
+    }])));
+}
+
+/**
+ * Bundles web resources using rspack. All compilations run as a single multi-compiler, so they
+ * share module resolution and run in parallel.
+ * @param {string} compilations - The compilations as a JSON array (see `createConfig`).
+ * @returns {Promise} - Resolves with the report of each compilation as JSON, by name, rejects if rspack couldn't run.
+ */
+async function web_bundling(compilations) {
+    compilations = safelyParseJSON(compilations) ?? [];
+    // The profiles are set by MetaSSR, so an unknown one is a bug rather than a diagnostic
+    const unknown = compilations.find(({ profile }) => !Object.hasOwn(profiles, profile));
+    if (unknown) {
+        throw `Unknown bundling profile \`${unknown.profile}\` of compilation \`${unknown.name}\``;
+    }
+
+    let compiler;
+    try {
+        // Invalid configurations are rejected when the compiler is created
+        compiler = rspack(await createConfigs(compilations));
+    } catch (error) {
+        return configFailure(compilations, error);
+    }
+
+    // Return a promise that runs the bundling process and resolves with the compilations' reports
     return new Promise((resolve, reject) => {
-        return compiler.run((error, stats) => {
-            // Handle errors during the bundling process
-            if (error) {
-                reject(error.message); // Reject with the error message if bundling fails
-            }
-
-            // Check if there are any errors in the bundling stats
-            if (error || stats?.hasErrors()) {
-                reject(stats.toString("errors-only")); // Reject with errors-only details from stats
-            }
-            resolve(0); // Resolve successfully when bundling is complete
+        return compiler.run((error, multiStats) => {
+            // The stats are ordered as the compilations
+            const reports = error ? null : JSON.stringify(Object.fromEntries(
+                multiStats.stats.map((stats, i) => [compilations[i].name, report(stats)])
+            ));
+
+            // The compiler is closed before returning, which writes the persistent cache and
+            // releases its handles, even if the bundling process itself failed
+            compiler.close(() => (error ? reject(error.message) : resolve(reports)));
         });
     });
 }
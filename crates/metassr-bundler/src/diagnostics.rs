@@ -0,0 +1,158 @@
+use metassr_utils::code_frame::SourceLocation;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, error::Error, fmt::Display, path::PathBuf};
+
+/// An error or a warning reported by the bundler.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// The module the diagnostic is about, relative to the project root (e.g. `./src/pages/index.jsx`).
+    pub module: Option<String>,
+    /// The absolute path of the module's file.
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// A diagnostic that isn't about a module, e.g. a failure of the bundler itself.
+    pub fn new(message: &str) -> Self {
+        Self {
+            module: None,
+            file: None,
+            line: None,
+            column: None,
+            message: message.to_string(),
+        }
+    }
+
+    /// The location of the diagnostic in the module's file, if known.
+    pub fn location(&self) -> Option<SourceLocation> {
+        Some(SourceLocation::new(
+            self.file.clone()?,
+            self.line?,
+            self.column.unwrap_or(1),
+        ))
+    }
+
+    /// The name of the file the diagnostic belongs to.
+    pub fn source(&self) -> String {
+        match (&self.module, &self.file) {
+            (Some(module), _) => module.clone(),
+            (None, Some(file)) => file.display().to_string(),
+            (None, None) => "<unknown>".to_string(),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let position = match (self.line, self.column) {
+            (Some(line), Some(column)) => format!("{line}:{column}  "),
+            (Some(line), None) => format!("{line}  "),
+            _ => String::new(),
+        };
+        write!(f, "{position}{}", self.message.trim_end())
+    }
+}
+
+/// The errors and warnings of a bundling run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostics {
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Groups `diagnostics` by the file they belong to, sorted by file name.
+pub fn group_by_file(diagnostics: &[Diagnostic]) -> BTreeMap<String, Vec<&Diagnostic>> {
+    let mut groups: BTreeMap<String, Vec<&Diagnostic>> = BTreeMap::new();
+    for diagnostic in diagnostics {
+        groups
+            .entry(diagnostic.source())
+            .or_default()
+            .push(diagnostic);
+    }
+    groups
+}
+
+/// Formats a group of diagnostics of the same file, one indented entry per diagnostic.
+pub fn format_group(file: &str, diagnostics: &[&Diagnostic]) -> String {
+    let mut output = file.to_string();
+    for diagnostic in diagnostics {
+        for line in diagnostic.to_string().lines() {
+            output.push_str("\n  ");
+            output.push_str(line);
+        }
+    }
+    output
+}
+
+/// Returned by [`WebBundler::exec`](crate::WebBundler::exec) when the bundler reports errors.
+#[derive(Debug, Clone)]
+pub struct BundlingError {
+    pub errors: Vec<Diagnostic>,
+}
+
+impl Display for BundlingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Bundling failed with {} error{}:",
+            self.errors.len(),
+            if self.errors.len() == 1 { "" } else { "s" }
+        )?;
+        for (file, diagnostics) in group_by_file(&self.errors) {
+            write!(f, "\n{}", format_group(&file, &diagnostics))?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for BundlingError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(module: &str, line: usize, message: &str) -> Diagnostic {
+        Diagnostic {
+            module: Some(module.to_string()),
+            file: None,
+            line: Some(line),
+            column: Some(1),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn formatting_errors_grouped_by_file() {
+        let error = BundlingError {
+            errors: vec![
+                diagnostic("./src/pages/index.jsx", 3, "Unexpected token"),
+                diagnostic("./src/_app.jsx", 1, "Module not found"),
+                diagnostic("./src/pages/index.jsx", 7, "Expected `}`\n  > 7 | }"),
+            ],
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "Bundling failed with 3 errors:\n\
+             ./src/_app.jsx\n  1:1  Module not found\n\
+             ./src/pages/index.jsx\n  3:1  Unexpected token\n  7:1  Expected `}`\n    > 7 | }"
+        );
+    }
+
+    #[test]
+    fn parsing_diagnostics() {
+        let diagnostics: Diagnostics = serde_json::from_str(
+            r#"{"errors":[{"module":"./src/a.js","file":"/app/src/a.js","line":2,"column":4,"message":"oops"}],"warnings":[]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            diagnostics.errors[0].location(),
+            Some(SourceLocation::new("/app/src/a.js".into(), 2, 4))
+        );
+        assert!(diagnostics.warnings.is_empty());
+    }
+}
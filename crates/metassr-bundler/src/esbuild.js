@@ -0,0 +1,331 @@
+const crypto = require('crypto');
+const fs = require('fs');
+const path = require('path');
+
+// Map stack traces of the bundled scripts back to their sources, so render errors point to
+// the project's files.
+process.setSourceMapsEnabled?.(true);
+
+/**
+ * Safely parses a JSON string, returning undefined if parsing fails.
+ * @param {string} json - The JSON string to parse.
+ * @returns {Object|undefined} - Parsed object or undefined if parsing fails.
+ */
+function safelyParseJSON(json) {
+    try {
+        return JSON.parse(json);
+    } catch (_) {
+        return undefined;
+    }
+}
+
+/**
+ * Returns the dependencies of a project's `package.json`. They are installed wherever the
+ * server runs, so the server bundle `require`s them instead of bundling them.
+ * @param {string} root - The project root.
+ * @returns {Array<string>} - The names of the dependencies.
+ */
+function projectDependencies(root) {
+    try {
+        const pkg = JSON.parse(fs.readFileSync(path.join(path.resolve(root), 'package.json'), 'utf8'));
+        return Object.keys(pkg.dependencies ?? {});
+    } catch (_) {
+        return [];
+    }
+}
+
+// Source map styles, as esbuild `sourcemap` values.
+const sourcemaps = {
+    none: false,
+    hidden: 'external', // Emitted but not referenced by the bundles, e.g. for error trackers
+    full: 'linked',
+};
+
+// Image and font extensions, emitted under `assets/`.
+const ASSETS = ['.png', '.jpg', '.jpeg', '.gif', '.webp', '.avif', '.svg', '.woff', '.woff2'];
+
+// The engines esbuild lowers syntax for, by browserslist browser name.
+const ENGINES = {
+    chrome: 'chrome', and_chr: 'chrome', edge: 'edge', firefox: 'firefox', and_ff: 'firefox',
+    safari: 'safari', ios_saf: 'ios', opera: 'opera', ie: 'ie',
+};
+
+/**
+ * Returns the esbuild targets of a profile. The browserslist queries of client bundles are
+ * resolved by the project's `browserslist` package, to the oldest version of each engine
+ * esbuild knows. Server bundles target the Node version running MetaSSR.
+ * @param {boolean} client - Whether the build is for the client.
+ * @param {Array<string>} browsers - The browserslist queries of the targeted browsers.
+ * @returns {Array<string>|undefined} - The esbuild targets, undefined to leave the syntax as is.
+ */
+function esbuildTargets(client, browsers = []) {
+    if (!client) {
+        return [`node${process.versions.node}`];
+    }
+    if (!browsers.length) {
+        return undefined;
+    }
+
+    let browserslist;
+    try {
+        browserslist = require(require.resolve('browserslist', { paths: [process.cwd()] }));
+    } catch (_) {
+        throw 'browserslist is not installed: add it to the dependencies of the project to target browsers with esbuild';
+    }
+    const oldest = new Map();
+    for (const browser of browserslist(browsers)) {
+        const [name, versions] = browser.split(' ');
+        const engine = ENGINES[name];
+        // Ranges (e.g. `ios_saf 15.2-15.3`) start at their oldest version, previews (e.g.
+        // `safari TP`) have none
+        const version = versions?.split('-')[0];
+        if (engine && !Number.isNaN(Number(version)) && !(Number(oldest.get(engine)) <= Number(version))) {
+            oldest.set(engine, version);
+        }
+    }
+    return [...oldest].map(([engine, version]) => `${engine}${version}`);
+}
+
+/**
+ * Returns a plugin resolving module requests to files. Unlike esbuild's `alias`, it takes
+ * absolute paths.
+ * @param {Object} alias - The absolute paths module requests are resolved to, by request.
+ * @returns {Object} - The esbuild plugin.
+ */
+function aliasPlugin(alias) {
+    const requests = Object.keys(alias).map((request) => request.replace(/[.*+?^${}()|[\]\\]/g, '\\$&'));
+    return {
+        name: 'metassr-alias',
+        setup(build) {
+            if (requests.length) {
+                build.onResolve({ filter: new RegExp(`^(${requests.join('|')})$`) }, (args) => ({ path: alias[args.path] }));
+            }
+        },
+    };
+}
+
+/**
+ * Creates the esbuild options of a compilation.
+ * @param {Object} compilation - The compilation to configure (see `createConfig` in `bundle.js`).
+ * @returns {Object} - The esbuild options.
+ */
+function createOptions({
+    entry, dist, profile, mode = 'production', sourceMaps = 'hidden', alias = {}, define = {},
+    targets = {}, publicPath = '/', externals: root = null, context = null,
+}) {
+    const client = profile !== 'server';
+    const dependencies = client || !root ? [] : projectDependencies(root);
+    // Render errors are mapped back to the sources by Node, which needs the maps referenced
+    const sourcemap = !client && sourceMaps !== 'none'
+        ? sourcemaps.full
+        : sourcemaps[sourceMaps] ?? sourcemaps.hidden;
+
+    return {
+        entryPoints: entry,
+        absWorkingDir: path.resolve(context ?? '.'), // Paths in the bundles are relative to it
+        outdir: path.join(process.cwd(), dist),
+        bundle: true,
+        write: false, // Files are written once named, see `emit`
+        metafile: true,
+        platform: client ? 'browser' : 'node',
+        target: esbuildTargets(client, targets.browsers), // Polyfills aren't injected, only the syntax is lowered
+        format: client ? 'iife' : 'cjs', // Render functions are exported to be called via metacall
+        jsx: 'automatic', // Use React's automatic JSX runtime
+        loader: {
+            '.js': 'jsx',
+            // Images and fonts are always emitted as files, esbuild has no size threshold
+            ...Object.fromEntries(ASSETS.map((ext) => [ext, 'file'])),
+            '.module.css': 'local-css', // CSS Modules, named from the file and class names in both profiles
+        },
+        assetNames: 'assets/[name].[hash]',
+        publicPath, // The URL assets are served under, e.g. `/dist/`
+        minify: mode !== 'development',
+        // Public environment variables, e.g. `process.env.METASSR_PUBLIC_API_URL`
+        define: { ...define, 'process.env.NODE_ENV': JSON.stringify(mode) },
+        plugins: [aliasPlugin(alias)], // Modules generated by the build, e.g. `metassr/image`
+        sourcemap,
+        // Keep the project's dependencies (and their subpaths, e.g. `react-dom/server`) external
+        external: dependencies.flatMap((dep) => [dep, `${dep}/*`]),
+        logLevel: 'silent', // Diagnostics are reported to MetaSSR instead
+    };
+}
+
+/**
+ * Converts esbuild errors or warnings to plain diagnostics.
+ * @param {Array} messages - The errors or warnings of a build.
+ * @returns {Array} - Diagnostics with their module, file, line, column and message.
+ */
+function toDiagnostics(messages = []) {
+    return messages.map(({ text, location }) => ({
+        module: location?.file ? `./${location.file}` : null,
+        file: location?.file ? path.resolve(location.file) : null,
+        line: location?.line ?? null,
+        column: location ? location.column + 1 : null, // esbuild columns start at 0
+        message: text,
+    }));
+}
+
+/**
+ * Inserts the content hash of a file in its name, as rspack does: `index.js.js` becomes
+ * `index.js.1a2b3c4d.js`.
+ * @param {string} file - The path of the file.
+ * @param {Uint8Array} contents - The contents of the file.
+ * @returns {string} - The hashed path.
+ */
+function hashed(file, contents) {
+    const hash = crypto.createHash('sha256').update(contents).digest('hex').slice(0, 8);
+    const ext = path.extname(file);
+    return `${file.slice(0, -ext.length)}.${hash}${ext}`;
+}
+
+/**
+ * Writes the output files of a build. Client bundles get content-hashed names, so browsers can
+ * cache them forever. Assets and stylesheets are only written by client builds: the server
+ * bundles reference the same URLs, and only use the class names of CSS Modules.
+ * @param {Array} outputFiles - The output files of the build.
+ * @param {string} outdir - The output directory.
+ * @param {boolean} client - Whether the build is for the client.
+ * @returns {Map} - The written paths, by the paths esbuild gave the files.
+ */
+function emit(outputFiles, outdir, client) {
+    const maps = new Map(outputFiles.map((file) => [file.path, file]));
+    const written = new Map();
+    for (const file of outputFiles) {
+        const asset = path.relative(outdir, file.path).startsWith(`assets${path.sep}`);
+        const stylesheet = /\.css(\.map)?$/.test(file.path);
+        if (file.path.endsWith('.map') || ((asset || stylesheet) && !client)) {
+            continue;
+        }
+
+        const emitted = client && !asset ? hashed(file.path, file.contents) : file.path;
+        const map = maps.get(`${file.path}.map`);
+        // Linked source maps are referenced by the file's name, which the hash changed
+        const contents = map && emitted !== file.path
+            ? file.text.replace(
+                `sourceMappingURL=${path.basename(file.path)}.map`,
+                `sourceMappingURL=${path.basename(emitted)}.map`,
+            )
+            : file.contents;
+
+        fs.mkdirSync(path.dirname(emitted), { recursive: true });
+        fs.writeFileSync(emitted, contents);
+        written.set(file.path, emitted);
+        if (map) {
+            fs.writeFileSync(`${emitted}.map`, map.contents);
+        }
+    }
+    return written;
+}
+
+/**
+ * Returns the project's source files among the inputs of an output, leaving out dependencies.
+ * @param {Object} inputs - The inputs of an output in the metafile.
+ * @returns {Array<string>} - The absolute paths of the files.
+ */
+function sourceFiles(inputs = {}) {
+    return Object.keys(inputs)
+        .map((input) => path.resolve(input))
+        .filter((input) => !input.includes(`${path.sep}node_modules${path.sep}`) && fs.existsSync(input));
+}
+
+/**
+ * Returns the asset files an output references, e.g. images imported by scripts and fonts
+ * loaded by stylesheets.
+ * @param {Object} output - The output in the metafile.
+ * @param {string} outdir - The output directory.
+ * @returns {Array<string>} - The absolute paths of the assets.
+ */
+function assetFiles(output, outdir) {
+    return (output?.imports ?? [])
+        .filter(({ kind }) => kind === 'file-loader' || kind === 'url-token')
+        .map(({ path: file }) => path.resolve(file))
+        .filter((file) => path.relative(outdir, file).startsWith(`assets${path.sep}`));
+}
+
+/**
+ * Reports the results of a build as `bundle.js` does: its warnings, its duration, and the
+ * files written for each entry.
+ * @param {Object} result - The result of the build.
+ * @param {string} outdir - The output directory.
+ * @param {Map} written - The written paths, as returned by `emit`.
+ * @param {number} time - How long the build took, in milliseconds.
+ * @returns {Object} - The errors, warnings, time and entrypoints of the build.
+ */
+function report(result, outdir, written, time) {
+    const relative = (file) => path.relative(outdir, written.get(file) ?? file).split(path.sep).join('/');
+    return {
+        errors: toDiagnostics(result.errors),
+        warnings: toDiagnostics(result.warnings),
+        time,
+        entrypoints: Object.fromEntries(
+            Object.entries(result.metafile.outputs)
+                .filter(([, output]) => output.entryPoint)
+                .map(([out, output]) => {
+                    const file = path.resolve(out);
+                    // Stylesheets are only written for the client
+                    const css = [output.cssBundle].filter(Boolean).map((css) => path.resolve(css))
+                        .filter((css) => written.has(css));
+                    return [
+                        // Entries are written to `<name>.js`
+                        path.relative(outdir, file).split(path.sep).join('/').replace(/\.js$/, ''),
+                        {
+                            files: [...css, file].map(relative),
+                            chunks: [], // Dynamic imports are bundled into the entry
+                            assets: [...new Set([
+                                ...assetFiles(output, outdir),
+                                ...assetFiles(result.metafile.outputs[output.cssBundle], outdir),
+                            ])].map(relative),
+                            modules: sourceFiles(output.inputs),
+                        },
+                    ];
+                })
+        ),
+    };
+}
+
+/**
+ * Bundles web resources using esbuild. The compilations are built in parallel.
+ * @param {string} compilations - The compilations as a JSON array (see `createConfig` in `bundle.js`).
+ * @returns {Promise} - Resolves with the report of each compilation as JSON, by name, rejects if esbuild couldn't run.
+ */
+async function esbuild_bundling(compilations) {
+    let esbuild;
+    try {
+        esbuild = require('esbuild');
+    } catch (_) {
+        throw 'esbuild is not installed: add it to the dependencies of the project to bundle with it';
+    }
+
+    compilations = safelyParseJSON(compilations) ?? [];
+    // The profiles are set by MetaSSR, so an unknown one is a bug rather than a diagnostic
+    const unknown = compilations.find(({ profile }) => profile !== 'client' && profile !== 'server');
+    if (unknown) {
+        throw `Unknown bundling profile \`${unknown.profile}\` of compilation \`${unknown.name}\``;
+    }
+
+    const reports = await Promise.all(compilations.map(async (compilation) => {
+        const start = Date.now();
+        const options = createOptions(compilation);
+        try {
+            const result = await esbuild.build(options);
+            const written = emit(result.outputFiles, options.outdir, compilation.profile !== 'server');
+            return [compilation.name, report(result, options.outdir, written, Date.now() - start)];
+        } catch (error) {
+            // Failed builds carry their diagnostics, anything else means esbuild couldn't run
+            if (!error.errors) {
+                throw error.message ?? String(error);
+            }
+            return [compilation.name, {
+                errors: toDiagnostics(error.errors),
+                warnings: toDiagnostics(error.warnings),
+                time: Date.now() - start,
+                entrypoints: {},
+            }];
+        }
+    }));
+    return JSON.stringify(Object.fromEntries(reports));
+}
+
+module.exports = {
+    esbuild_bundling // Export the esbuild_bundling function to call it via metacall
+};
@@ -1,46 +1,120 @@
+pub mod backends;
+pub mod diagnostics;
+pub mod options;
+
 use anyhow::{anyhow, Result};
-use lazy_static::lazy_static;
-use metacall::{loaders, metacall, MetacallFuture, MetacallValue};
-use metassr_utils::checker::CheckerState;
+use backends::{rspack::BUILD_SCRIPT, Bundler};
+use diagnostics::{BundlingError, Diagnostic};
+use metassr_utils::hash::content_hash;
+use options::{BundleOptions, Targets};
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     ffi::OsStr,
+    fmt::Display,
+    fs,
+    future::Future,
     marker::Sized,
-    path::Path,
-    sync::{Arc, Condvar, Mutex},
+    path::{Path, PathBuf},
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake},
+    thread::{self, Thread},
 };
-use tracing::error;
 
-lazy_static! {
-    /// A detector for if the bundling script `./bundle.js` is loaded or not. It is used to solve multiple loading script error in metacall.
-    static ref IS_BUNDLING_SCRIPT_LOADED: Mutex<CheckerState> = Mutex::new(CheckerState::default());
+/// The project's files that pin its dependencies. Bundles depend on them, even when their
+/// dependencies are left as `require` calls.
+pub const DEPENDENCY_FILES: [&str; 4] = [
+    "package.json",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+];
+
+/// The project's stylesheet configurations, in the project root: the PostCSS ones, which make
+/// stylesheets go through `postcss-loader` (see `POSTCSS_CONFIGS` in `bundle.js`), and the
+/// Tailwind ones, read by its PostCSS plugin.
+pub const STYLE_CONFIGS: [&str; 12] = [
+    "postcss.config.js",
+    "postcss.config.cjs",
+    "postcss.config.mjs",
+    "postcss.config.json",
+    ".postcssrc",
+    ".postcssrc.json",
+    ".postcssrc.js",
+    ".postcssrc.cjs",
+    "tailwind.config.js",
+    "tailwind.config.cjs",
+    "tailwind.config.mjs",
+    "tailwind.config.ts",
+];
 
-    /// A simple checker to check if the bundling function is done or not. It is used to block the program until bundling done.
-    static ref IS_COMPLIATION_WAIT: Arc<CompilationWait> = Arc::new(CompilationWait::default());
+/// The project's bundler configuration hook, in the project root. It exports a function
+/// customizing the rspack configuration of each compilation: `(config, { target, mode }) => config`.
+pub const BUNDLER_CONFIG: &str = "metassr.bundler.js";
+
+/// The files emitted for an entry, relative to the output directory.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Entrypoint {
+    /// The files loaded along with the entry, in dependency order: the shared chunks
+    /// (e.g. `chunks/vendor.5e6f7a8b.js`) come before the entry's own files
+    /// (e.g. `pages/index.js.1a2b3c4d.js`).
+    pub files: Vec<String>,
+    /// The chunks the entry loads on demand through dynamic `import()`.
+    #[serde(default)]
+    pub chunks: Vec<String>,
+    /// The images and fonts emitted under `assets/` that the entry's files reference (e.g.
+    /// `assets/logo.3f2a9c1e.png`). Smaller ones are inlined into the files instead.
+    #[serde(default)]
+    pub assets: Vec<String>,
+    /// The absolute paths of the project's files bundled into the entry and its chunks.
+    /// Dependencies from `node_modules` are left out.
+    #[serde(default)]
+    pub modules: Vec<PathBuf>,
 }
-static BUILD_SCRIPT: &str = include_str!("./bundle.js");
-const BUNDLING_FUNC: &str = "web_bundling";
 
-/// A simple struct for compilation wait of the bundling function.
-struct CompilationWait {
-    checker: Mutex<CheckerState>,
-    cond: Condvar,
+/// What a bundling run produced.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BundleOutput {
+    /// The files emitted for each entry.
+    #[serde(default)]
+    pub entrypoints: HashMap<String, Entrypoint>,
+    /// The warnings reported by the bundler, which are logged as well.
+    #[serde(skip)]
+    pub warnings: Vec<Diagnostic>,
+    /// How long the bundling took, in milliseconds.
+    #[serde(default)]
+    pub time: u64,
 }
 
-impl Default for CompilationWait {
-    fn default() -> Self {
-        Self {
-            checker: Mutex::new(CheckerState::default()),
-            cond: Condvar::new(),
-        }
+/// The environment a bundle is built for, which selects the configuration of the bundler backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BundleProfile {
+    /// Scripts that run in the browser, such as the hydration scripts.
+    Client,
+    /// Scripts that run on the server in Node, such as the render scripts. The project's
+    /// dependencies are left as `require` calls when [`WebBundler::externals`] is set, and
+    /// asset files are referenced with the same URLs as the client bundle without being
+    /// emitted again.
+    Server,
+}
+
+impl Display for BundleProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Client => "client",
+            Self::Server => "server",
+        })
     }
 }
 
-/// A web bundler that invokes the `web_bundling` function from the Node.js `bundle.js` script
-/// using MetaCall. It is designed to bundle web resources like JavaScript and TypeScript files
-/// by calling a custom `rspack` configuration.
+/// A web bundler that bundles web resources like JavaScript and TypeScript files through a
+/// [`Bundler`](backends::Bundler) backend: a custom `rspack` configuration by default, or
+/// `esbuild`, both run in Node via MetaCall.
 ///
-/// The `exec` function blocks the execution until the bundling process completes.
+/// The `exec` function blocks the execution until the bundling process completes, while
+/// `bundle` returns a future of it.
 #[derive(Debug)]
 pub struct WebBundler<'a> {
     /// A map containing the source entry points for bundling.
@@ -48,6 +122,30 @@ pub struct WebBundler<'a> {
     pub targets: HashMap<String, &'a Path>,
     /// The output directory where the bundled files will be stored.
     pub dist_path: &'a Path,
+    /// Whether the bundle is built for the browser or for the server.
+    pub profile: BundleProfile,
+    /// The build mode and source maps, production ones by default.
+    pub options: BundleOptions,
+    /// Where rspack persists its cache between builds, if it does.
+    pub cache_dir: Option<PathBuf>,
+    /// Module requests resolved to files instead of packages, e.g. `metassr/image`.
+    pub aliases: HashMap<String, PathBuf>,
+    /// Expressions replaced at build time, e.g. `process.env.METASSR_PUBLIC_API_URL`, with the
+    /// code replacing them.
+    pub defines: BTreeMap<String, String>,
+    /// The browsers client bundles are built for.
+    pub browser_targets: Targets,
+    /// The URL the emitted files are served under, e.g. `/dist/`.
+    pub public_path: String,
+    /// The project root whose dependencies server bundles leave external, if they do.
+    pub externals: Option<PathBuf>,
+    /// The bundler the bundle is built with instead of the backend of the options, if set.
+    pub backend: Option<Arc<dyn Bundler>>,
+    /// The configuration hook customizing the bundle, [`BUNDLER_CONFIG`] by default.
+    pub config_hook: PathBuf,
+    /// The directory the module IDs and source map paths are relative to, the current
+    /// directory by default.
+    pub context: Option<PathBuf>,
 }
 
 impl<'a> WebBundler<'a> {
@@ -55,9 +153,14 @@ impl<'a> WebBundler<'a> {
     ///
     /// - `targets`: A HashMap where the key is a string representing an entry point, and the value is the file path.
     /// - `dist_path`: The path to the directory where the bundled output should be saved.
+    /// - `profile`: The environment the bundle is built for.
     ///
     /// Returns a `WebBundler` struct.
-    pub fn new<S>(targets: &'a HashMap<String, String>, dist_path: &'a S) -> Result<Self>
+    pub fn new<S>(
+        targets: &'a HashMap<String, String>,
+        dist_path: &'a S,
+        profile: BundleProfile,
+    ) -> Result<Self>
     where
         S: AsRef<OsStr> + ?Sized,
     {
@@ -80,82 +183,360 @@ impl<'a> WebBundler<'a> {
             ));
         }
 
+        let dist_path = Path::new(dist_path);
         Ok(Self {
             targets,
-            dist_path: Path::new(dist_path),
+            dist_path,
+            profile,
+            options: BundleOptions::default(),
+            cache_dir: None,
+            aliases: HashMap::new(),
+            defines: BTreeMap::new(),
+            browser_targets: Targets::default(),
+            public_path: default_public_path(dist_path),
+            externals: None,
+            backend: None,
+            config_hook: PathBuf::from(BUNDLER_CONFIG),
+            context: None,
         })
     }
 
-    /// Executes the bundling process by invoking the `web_bundling` function from `bundle.js` via MetaCall.
+    /// Persists rspack's cache in `dir`, so the next builds only rebuild the changed modules.
+    /// The cache is bypassed when it's disabled by the options.
+    pub fn cache<P: AsRef<Path> + ?Sized>(mut self, dir: &P) -> Self {
+        self.cache_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Resolves imports of `request` to the file at `path`, so modules generated by the build
+    /// can be imported like packages.
+    pub fn alias<S: ToString, P: AsRef<Path> + ?Sized>(mut self, request: S, path: &P) -> Self {
+        self.aliases
+            .insert(request.to_string(), path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Replaces `expression` in the bundled modules with `value`, as a JSON string literal.
+    pub fn define<S: ToString, V: AsRef<str> + ?Sized>(mut self, expression: S, value: &V) -> Self {
+        self.defines.insert(
+            expression.to_string(),
+            serde_json::to_string(value.as_ref()).unwrap(),
+        );
+        self
+    }
+
+    /// Sets the browsers the bundle is built for, with their polyfills. Only client bundles
+    /// use them.
+    pub fn browser_targets(mut self, targets: Targets) -> Self {
+        self.browser_targets = targets;
+        self
+    }
+
+    /// Sets the URL the emitted files are served under, for bundles referencing the assets of
+    /// another output directory. Defaults to [`default_public_path`] of the output directory.
+    pub fn public_path(mut self, url: &str) -> Self {
+        self.public_path = url.to_owned();
+        self
+    }
+
+    /// Leaves the dependencies of the `package.json` in `root` external to server bundles:
+    /// they're `require`d at runtime instead of being bundled. Client bundles ignore it.
+    pub fn externals<P: AsRef<Path> + ?Sized>(mut self, root: &P) -> Self {
+        self.externals = Some(root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Customizes the configuration of the bundle with the hook at `path` instead of the
+    /// project's [`BUNDLER_CONFIG`]. Missing hooks leave the configuration as is.
+    pub fn config_hook<P: AsRef<Path> + ?Sized>(mut self, path: &P) -> Self {
+        self.config_hook = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Makes the module IDs and source map paths of the bundle relative to `root`, e.g. the
+    /// project root, instead of the current directory, so the bundle is the same wherever
+    /// the project is.
+    pub fn context<P: AsRef<Path> + ?Sized>(mut self, root: &P) -> Self {
+        self.context = Some(root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Builds the bundle with `backend` instead of the backend selected by the options, e.g.
+    /// a bundler provided by the caller.
+    pub fn backend(mut self, backend: Arc<dyn Bundler>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// The bundler the bundle is built with: the one set by [`WebBundler::backend`], or the
+    /// backend of the options.
+    fn bundler(&self) -> &dyn Bundler {
+        match &self.backend {
+            Some(backend) => backend.as_ref(),
+            None => self.options.backend.bundler(),
+        }
+    }
+
+    /// Whether this bundle and `other` are built by the same bundler, so they can be built
+    /// in the same run.
+    fn shares_bundler(&self, other: &Self) -> bool {
+        match (&self.backend, &other.backend) {
+            (Some(backend), Some(other)) => Arc::ptr_eq(backend, other),
+            (None, None) => self.options.backend == other.options.backend,
+            _ => false,
+        }
+    }
+
+    /// The persistent cache settings passed to `bundle.js`, if the cache is used.
     ///
-    /// It checks if the bundling script has been loaded, then calls the function and waits for the
-    /// bundling to complete, either resolving successfully or logging an error.
+    /// The cache is invalidated by its version: a hash of the bundler configuration, the
+    /// MetaSSR version, the bundle's profile, options, defines, targets, public path and
+    /// externals, the project's dependency files and the bundle's configuration hook.
+    fn cache_settings(&self) -> Option<CacheSettings> {
+        let directory = self.cache_dir.clone().filter(|_| self.options.cache)?;
+
+        let mut key = format!(
+            "{BUILD_SCRIPT}\n{}\n{} {} {}\n{}\n{} {:?} {:?}",
+            env!("CARGO_PKG_VERSION"),
+            self.profile,
+            self.options.mode,
+            self.options.source_maps(),
+            self.browser_targets,
+            self.public_path,
+            self.externals,
+            self.context
+        );
+        let files = DEPENDENCY_FILES.iter().chain(&STYLE_CONFIGS).map(Path::new);
+        for file in files.chain([self.config_hook.as_path()]) {
+            if let Ok(content) = fs::read_to_string(file) {
+                key.push_str(&format!("\n{}\n{content}", file.display()));
+            }
+        }
+        for (expression, value) in &self.defines {
+            key.push_str(&format!("\n{expression}={value}"));
+        }
+
+        Some(CacheSettings {
+            directory,
+            version: content_hash(key.as_bytes()),
+        })
+    }
+
+    /// The compilation `name` of this bundle, as passed to the bundler backend.
+    fn compilation<'b>(&'b self, name: &'b str) -> Compilation<'b> {
+        Compilation {
+            name,
+            entry: &self.targets,
+            dist: self.dist_path,
+            profile: self.profile,
+            mode: self.options.mode,
+            source_maps: self.options.source_maps(),
+            cache: self.cache_settings(),
+            alias: &self.aliases,
+            define: &self.defines,
+            targets: &self.browser_targets,
+            public_path: &self.public_path,
+            externals: self.externals.as_deref(),
+            config_hook: &self.config_hook,
+            context: self.context.as_deref(),
+        }
+    }
+
+    /// Sets the build mode and source maps of the bundle.
+    pub fn options(mut self, options: BundleOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Executes the bundling process through the backend set by [`WebBundler::backend`], or
+    /// the one selected by the options (rspack by default), blocking the current thread until the bundling completes. Use
+    /// [`WebBundler::bundle`] from async code.
     ///
     /// # Errors
     ///
     /// This function returns an `Err` if the bundling script cannot be loaded or if bundling fails.
-    pub fn exec(&self) -> Result<()> {
-        // Lock the mutex to check if the bundling script is already loaded
-        let mut guard = IS_BUNDLING_SCRIPT_LOADED.lock().unwrap();
-        if !guard.is_true() {
-            // If not loaded, attempt to load the script into MetaCall
-            if let Err(e) = loaders::from_memory("node", BUILD_SCRIPT) {
-                return Err(anyhow!("Cannot load bundling script: {e:?}"));
+    /// When the bundler reports errors, the `Err` is a [`BundlingError`] holding them.
+    pub fn exec(&self) -> Result<BundleOutput> {
+        block_on(self.bundle())
+    }
+
+    /// Bundles like [`WebBundler::exec`], without blocking: the returned future resolves once
+    /// the bundling completes. Several bundles can run at the same time, each one resolving
+    /// its own future.
+    ///
+    /// The bundler's warnings are logged, and returned along with the emitted files.
+    ///
+    /// # Errors
+    ///
+    /// Fails as [`WebBundler::exec`] does.
+    pub async fn bundle(&self) -> Result<BundleOutput> {
+        let bundling = self
+            .bundler()
+            .bundle(&[self.compilation(SINGLE_COMPILATION)])?;
+        let mut outputs = bundling.await?;
+        Ok(outputs.remove(SINGLE_COMPILATION).unwrap_or_default())
+    }
+}
+
+/// Returns the URL the files emitted in `dist` are served under: the directory's name, at the
+/// root of the site (e.g. `/dist/` for `dist` and `app/dist`).
+pub fn default_public_path(dist: &Path) -> String {
+    match dist.file_name() {
+        Some(name) => format!("/{}/", name.to_string_lossy()),
+        None => "/".to_owned(),
+    }
+}
+
+/// The name of the compilation of a bundler run on its own.
+const SINGLE_COMPILATION: &str = "bundle";
+
+/// Where and under which version a backend persists the cache of a compilation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CacheSettings {
+    pub directory: PathBuf,
+    pub version: String,
+}
+
+/// A compilation, as bundler backends get it: entries bundled together into an output
+/// directory, for a profile.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Compilation<'a> {
+    /// The name of the compilation, unique within a bundling run.
+    pub name: &'a str,
+    /// The source entry points, by entry name.
+    pub entry: &'a HashMap<String, &'a Path>,
+    /// The output directory of the bundled files.
+    pub dist: &'a Path,
+    pub profile: BundleProfile,
+    pub mode: options::BuildMode,
+    pub source_maps: options::SourceMaps,
+    /// Where the modules are cached between builds, for backends that cache them.
+    pub cache: Option<CacheSettings>,
+    /// The files module requests are resolved to, by request.
+    pub alias: &'a HashMap<String, PathBuf>,
+    /// The code replacing expressions at build time, by expression.
+    pub define: &'a BTreeMap<String, String>,
+    /// The browsers the bundle is built for, if it's a client one.
+    pub targets: &'a Targets,
+    /// The URL the emitted files are served under.
+    pub public_path: &'a str,
+    /// The project root whose dependencies are left external, if it's a server bundle.
+    pub externals: Option<&'a Path>,
+    /// The configuration hook customizing the compilation.
+    pub config_hook: &'a Path,
+    /// The directory module IDs and source map paths are relative to, if it isn't the
+    /// current directory.
+    pub context: Option<&'a Path>,
+}
+
+/// Bundles several named compilations (e.g. the client and server bundles of a project) in a
+/// single run of their backend (a multi-compiler run with rspack), so they share module
+/// resolution and run in parallel. Compilations with different backends run side by side.
+///
+/// ```no_run
+/// # use metassr_bundler::{BundleProfile, MultiBundler, WebBundler};
+/// # use std::collections::HashMap;
+/// # fn main() -> anyhow::Result<()> {
+/// let targets = HashMap::from([("pages/home".to_owned(), "./src/home.js".to_owned())]);
+/// let outputs = MultiBundler::new()
+///     .add("client", WebBundler::new(&targets, "dist", BundleProfile::Client)?)
+///     .add("server", WebBundler::new(&targets, "dist", BundleProfile::Server)?)
+///     .exec()?;
+/// let client = &outputs["client"];
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct MultiBundler<'a> {
+    compilations: Vec<(String, WebBundler<'a>)>,
+}
+
+impl<'a> MultiBundler<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the compilation `name`, built by `bundler`. Names are unique: a compilation
+    /// replaces the one it has the name of.
+    pub fn add<S: ToString>(mut self, name: S, bundler: WebBundler<'a>) -> Self {
+        let name = name.to_string();
+        self.compilations.retain(|(other, _)| *other != name);
+        self.compilations.push((name, bundler));
+        self
+    }
+
+    /// Runs every compilation at once, returning what each of them produced by name.
+    ///
+    /// # Errors
+    ///
+    /// Fails as [`WebBundler::exec`] does. The errors of every compilation are returned together
+    /// in a single [`BundlingError`].
+    pub fn exec(&self) -> Result<HashMap<String, BundleOutput>> {
+        block_on(self.bundle())
+    }
+
+    /// Runs every compilation at once like [`MultiBundler::exec`], without blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails as [`MultiBundler::exec`] does.
+    pub async fn bundle(&self) -> Result<HashMap<String, BundleOutput>> {
+        // Every run is started before any is awaited, so they all make progress together
+        let mut groups: Vec<(&WebBundler, Vec<Compilation>)> = vec![];
+        for (name, bundler) in &self.compilations {
+            let compilation = bundler.compilation(name);
+            match groups
+                .iter_mut()
+                .find(|(other, _)| other.shares_bundler(bundler))
+            {
+                Some((_, compilations)) => compilations.push(compilation),
+                None => groups.push((bundler, vec![compilation])),
             }
-            // Mark the script as loaded
-            guard.make_true();
         }
-        // Drop the lock on the mutex as it's no longer needed
-        drop(guard);
+        let bundlings = groups
+            .iter()
+            .map(|(bundler, compilations)| bundler.bundler().bundle(compilations))
+            .collect::<Result<Vec<_>>>()?;
 
-        // Resolve callback when the bundling process is completed successfully
-        fn resolve(_: Box<dyn MetacallValue>, _: Box<dyn MetacallValue>) {
-            let compilation_wait = &*Arc::clone(&IS_COMPLIATION_WAIT);
-            let mut started = compilation_wait.checker.lock().unwrap();
+        let mut outputs = HashMap::new();
+        let mut errors = vec![];
+        for bundling in bundlings {
+            match bundling.await {
+                Ok(group) => outputs.extend(group),
+                Err(e) => match e.downcast::<BundlingError>() {
+                    Ok(e) => errors.extend(e.errors),
+                    Err(e) => return Err(e),
+                },
+            }
+        }
 
-            // Mark the process as completed and notify waiting threads
-            started.make_true();
-            compilation_wait.cond.notify_one();
+        if !errors.is_empty() {
+            return Err(BundlingError { errors }.into());
         }
+        Ok(outputs)
+    }
+}
 
-        // Reject callback for handling errors during the bundling process
-        fn reject(err: Box<dyn MetacallValue>, _: Box<dyn MetacallValue>) {
-            let compilation_wait = &*Arc::clone(&IS_COMPLIATION_WAIT);
-            let mut started = compilation_wait.checker.lock().unwrap();
+/// Wakes up the thread blocked on a future.
+struct ThreadWaker(Thread);
 
-            // Log the bundling error and mark the process as completed
-            error!("Bundling rejected: {err:?}");
-            started.make_true();
-            compilation_wait.cond.notify_one();
-        }
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
 
-        // Call the `web_bundling` function in the MetaCall script with targets and output path
-        let future = metacall::<MetacallFuture>(
-            BUNDLING_FUNC,
-            [
-                // Serialize the targets map to a string format
-                serde_json::to_string(&self.targets)?,
-                // Get the distribution path as a string
-                self.dist_path.to_str().unwrap().to_owned(),
-            ],
-        )
-        .unwrap();
-
-        // Set the resolve and reject handlers for the bundling future
-        future.then(resolve).catch(reject).await_fut();
-
-        // Lock the mutex and wait for the bundling process to complete
-        let compilation_wait = Arc::clone(&IS_COMPLIATION_WAIT);
-        let mut started = compilation_wait.checker.lock().unwrap();
-
-        // Block the current thread until the bundling process signals completion
-        while !started.is_true() {
-            started = Arc::clone(&IS_COMPLIATION_WAIT).cond.wait(started).unwrap();
+/// Runs `future` to completion, blocking the current thread.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
         }
-
-        // Reset the checker state to false after the process completes
-        started.make_false();
-        Ok(())
     }
 }
 
@@ -164,6 +545,7 @@ mod tests {
 
     use super::*;
     use metacall::switch;
+    use options::{Backend, BuildMode, SourceMaps};
 
     fn clean() {
         let dist = Path::new("test/dist");
@@ -178,10 +560,21 @@ mod tests {
         let _metacall = switch::initialize().unwrap();
         let targets = HashMap::from([("pages/home".to_owned(), "./tests/home.js".to_owned())]);
 
-        match WebBundler::new(&targets, "tests/dist") {
+        match WebBundler::new(&targets, "tests/dist", BundleProfile::Client) {
             Ok(bundler) => {
-                assert!(bundler.exec().is_ok());
-                assert!(Path::new("tests/dist/pages/home.js").exists());
+                let output = bundler.exec().unwrap();
+                let files = &output.entrypoints["pages/home"].files;
+                assert!(files
+                    .last()
+                    .is_some_and(|f| f.starts_with("pages/home.") && f.ends_with(".js")));
+                assert!(files
+                    .iter()
+                    .any(|f| f.starts_with("chunks/vendor.") && f.ends_with(".js")));
+                assert!(files
+                    .iter()
+                    .all(|f| Path::new("tests/dist").join(f).exists()));
+                let home = Path::new("tests/home.js").canonicalize().unwrap();
+                assert_eq!(output.entrypoints["pages/home"].modules, vec![home]);
             }
             Err(err) => {
                 panic!("BUNDLING TEST FAILED: {err:?}",)
@@ -190,13 +583,471 @@ mod tests {
         clean();
     }
 
+    #[test]
+    fn multi_bundling_works() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/home".to_owned(), "./tests/home.js".to_owned())]);
+
+        let outputs = MultiBundler::new()
+            .add(
+                "client",
+                WebBundler::new(&targets, "tests/dist/client", BundleProfile::Client).unwrap(),
+            )
+            .add(
+                "server",
+                WebBundler::new(&targets, "tests/dist/server", BundleProfile::Server).unwrap(),
+            )
+            .exec()
+            .unwrap();
+        assert!(outputs["client"].entrypoints["pages/home"]
+            .files
+            .iter()
+            .all(|f| Path::new("tests/dist/client").join(f).exists()));
+        assert!(outputs["server"].entrypoints["pages/home"]
+            .files
+            .contains(&"pages/home.js".to_owned()));
+        clean();
+    }
+
+    #[test]
+    fn concurrent_bundling() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/home".to_owned(), "./tests/home.js".to_owned())]);
+        let lazy = HashMap::from([("pages/lazy".to_owned(), "./tests/lazy.js".to_owned())]);
+
+        let home = WebBundler::new(&targets, "tests/dist/home", BundleProfile::Client).unwrap();
+        let lazy = WebBundler::new(&lazy, "tests/dist/lazy", BundleProfile::Client).unwrap();
+        // Each bundling waits for its own run, on its own thread
+        let (home, lazy) = std::thread::scope(|scope| {
+            let home = scope.spawn(|| home.exec());
+            let lazy = scope.spawn(|| lazy.exec());
+            (home.join().unwrap(), lazy.join().unwrap())
+        });
+        assert!(home.unwrap().entrypoints.contains_key("pages/home"));
+        assert!(lazy.unwrap().entrypoints.contains_key("pages/lazy"));
+        clean();
+    }
+
+    #[test]
+    fn persistent_cache_is_reused() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/home".to_owned(), "./tests/home.js".to_owned())]);
+        let cache = Path::new("tests/dist/cache/rspack");
+        let build = || {
+            WebBundler::new(&targets, "tests/dist", BundleProfile::Client)
+                .unwrap()
+                .cache(cache)
+                .exec()
+                .unwrap()
+        };
+        let entries = || {
+            let mut entries: Vec<_> = std::fs::read_dir(cache)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect();
+            entries.sort();
+            entries
+        };
+
+        // The cache is written once the bundling returns
+        let first = build();
+        let cached = entries();
+        assert!(!cached.is_empty());
+
+        // The second build is restored from it, rather than from a new cache
+        let second = build();
+        assert_eq!(
+            first.entrypoints["pages/home"].files,
+            second.entrypoints["pages/home"].files
+        );
+        assert_eq!(cached, entries());
+        clean();
+    }
+
+    #[test]
+    fn source_maps_follow_options() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/home".to_owned(), "./tests/home.js".to_owned())]);
+
+        let bundler = WebBundler::new(&targets, "tests/dist", BundleProfile::Client)
+            .unwrap()
+            .options(BundleOptions::new(BuildMode::Production, None));
+        let output = bundler.exec().unwrap();
+        let script = output.entrypoints["pages/home"]
+            .files
+            .last()
+            .unwrap()
+            .clone();
+        let bundle = std::fs::read_to_string(Path::new("tests/dist").join(&script)).unwrap();
+        assert!(Path::new("tests/dist")
+            .join(format!("{script}.map"))
+            .exists());
+        assert!(!bundle.contains("sourceMappingURL"));
+        clean();
+
+        let bundler = WebBundler::new(&targets, "tests/dist", BundleProfile::Client)
+            .unwrap()
+            .options(BundleOptions::new(
+                BuildMode::Development,
+                Some(SourceMaps::None),
+            ));
+        let output = bundler.exec().unwrap();
+        let script = output.entrypoints["pages/home"]
+            .files
+            .last()
+            .unwrap()
+            .clone();
+        assert!(!Path::new("tests/dist")
+            .join(format!("{script}.map"))
+            .exists());
+        clean();
+    }
+
+    #[test]
+    fn dynamic_imports_are_split() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/lazy".to_owned(), "./tests/lazy.js".to_owned())]);
+
+        let bundler = WebBundler::new(&targets, "tests/dist", BundleProfile::Client).unwrap();
+        let output = bundler.exec().unwrap();
+        let chunks = &output.entrypoints["pages/lazy"].chunks;
+        assert!(chunks
+            .iter()
+            .any(|f| f.starts_with("chunks/") && f.ends_with(".js")));
+        clean();
+    }
+
+    #[test]
+    fn css_modules_match_between_profiles() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/styled".to_owned(), "./tests/styled.js".to_owned())]);
+
+        let outputs = MultiBundler::new()
+            .add(
+                "client",
+                WebBundler::new(&targets, "tests/dist/client", BundleProfile::Client).unwrap(),
+            )
+            .add(
+                "server",
+                WebBundler::new(&targets, "tests/dist/server", BundleProfile::Server).unwrap(),
+            )
+            .exec()
+            .unwrap();
+
+        // The page's stylesheet is extracted by the client build only
+        let stylesheet = outputs["client"].entrypoints["pages/styled"]
+            .files
+            .iter()
+            .find(|f| f.ends_with(".css"))
+            .expect("the page's stylesheet is emitted");
+        assert!(!outputs["server"].entrypoints["pages/styled"]
+            .files
+            .iter()
+            .any(|f| f.ends_with(".css")));
+
+        // The scoped class name rendered by the server is the one the stylesheet defines
+        let css = std::fs::read_to_string(Path::new("tests/dist/client").join(stylesheet)).unwrap();
+        let class = css
+            .split(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+            .find(|word| word.starts_with("card-"))
+            .expect("the class name is scoped");
+        let server = std::fs::read_to_string("tests/dist/server/pages/styled.js").unwrap();
+        assert!(server.contains(class));
+        clean();
+    }
+
+    #[test]
+    fn large_assets_are_emitted() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/icons".to_owned(), "./tests/icons.js".to_owned())]);
+
+        let bundler = WebBundler::new(&targets, "tests/dist", BundleProfile::Client).unwrap();
+        let output = bundler.exec().unwrap();
+
+        // Only the asset above the threshold is emitted, the other one is inlined
+        let assets = &output.entrypoints["pages/icons"].assets;
+        assert_eq!(assets.len(), 1);
+        assert!(assets[0].starts_with("assets/large.") && assets[0].ends_with(".svg"));
+        assert!(Path::new("tests/dist").join(&assets[0]).exists());
+        clean();
+    }
+
+    #[test]
+    fn aliases_resolve_to_files() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets =
+            HashMap::from([("pages/aliased".to_owned(), "./tests/aliased.js".to_owned())]);
+        let greeting = Path::new("tests/greeting.js").canonicalize().unwrap();
+
+        let bundler = WebBundler::new(&targets, "tests/dist", BundleProfile::Server)
+            .unwrap()
+            .alias("metassr/greeting", &greeting);
+        let output = bundler.exec().unwrap();
+        assert!(output.entrypoints["pages/aliased"]
+            .modules
+            .contains(&greeting));
+        clean();
+    }
+
+    #[test]
+    fn browser_targets_lower_syntax() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/modern".to_owned(), "./tests/modern.js".to_owned())]);
+
+        let bundler = WebBundler::new(&targets, "tests/dist", BundleProfile::Client)
+            .unwrap()
+            .options(BundleOptions::new(
+                BuildMode::Development,
+                Some(SourceMaps::None),
+            ))
+            .browser_targets(Targets {
+                browsers: vec!["ie 11".to_owned()],
+                polyfills: false,
+            });
+        let output = bundler.exec().unwrap();
+
+        // Neither the module nor the runtime use arrow functions or template literals
+        let file = output.entrypoints["pages/modern"].files.last().unwrap();
+        let bundle = std::fs::read_to_string(Path::new("tests/dist").join(file)).unwrap();
+        assert!(bundle.contains("Hello, "));
+        assert!(!bundle.contains("=>") && !bundle.contains('`'));
+        clean();
+    }
+
+    #[test]
+    fn polyfills_require_core_js() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/modern".to_owned(), "./tests/modern.js".to_owned())]);
+
+        // `core-js` isn't installed in the crate, which the polyfills would be imported from
+        let err = WebBundler::new(&targets, "tests/dist", BundleProfile::Client)
+            .unwrap()
+            .browser_targets(Targets {
+                browsers: vec!["ie 11".to_owned()],
+                polyfills: true,
+            })
+            .exec()
+            .unwrap_err();
+        let err = err.downcast_ref::<BundlingError>().unwrap();
+        assert_eq!(err.errors.len(), 1);
+        assert_eq!(err.errors[0].module.as_deref(), Some("./package.json"));
+        assert!(err.errors[0].message.contains("`core-js` is not installed"));
+        clean();
+    }
+
+    #[test]
+    fn server_bundling_keeps_dependencies_external() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/home".to_owned(), "./tests/home.js".to_owned())]);
+
+        // The dependencies of the fixture's `package.json` are left external
+        let bundler = WebBundler::new(&targets, "tests/dist", BundleProfile::Server)
+            .unwrap()
+            .externals("tests");
+        assert!(bundler.exec().is_ok());
+        let bundle = std::fs::read_to_string("tests/dist/pages/home.js").unwrap();
+        assert!(bundle.contains(r#"require("react")"#));
+
+        // They're bundled unless externals are configured
+        let bundler = WebBundler::new(&targets, "tests/dist", BundleProfile::Server).unwrap();
+        assert!(bundler.exec().is_ok());
+        let bundle = std::fs::read_to_string("tests/dist/pages/home.js").unwrap();
+        assert!(!bundle.contains(r#"require("react")"#));
+        clean();
+    }
+
+    #[test]
+    fn esbuild_bundling_works() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/home".to_owned(), "./tests/home.js".to_owned())]);
+        let options = BundleOptions::default().backend(Backend::Esbuild);
+
+        let outputs = MultiBundler::new()
+            .add(
+                "client",
+                WebBundler::new(&targets, "tests/dist/client", BundleProfile::Client)
+                    .unwrap()
+                    .options(options),
+            )
+            .add(
+                "server",
+                WebBundler::new(&targets, "tests/dist/server", BundleProfile::Server)
+                    .unwrap()
+                    .options(options)
+                    .externals("tests"),
+            )
+            .exec()
+            .unwrap();
+        let files = &outputs["client"].entrypoints["pages/home"].files;
+        assert!(files.iter().all(
+            |f| f.starts_with("pages/home.") && Path::new("tests/dist/client").join(f).exists()
+        ));
+
+        let bundle = std::fs::read_to_string("tests/dist/server/pages/home.js").unwrap();
+        assert!(bundle.contains(r#"require("react")"#));
+        clean();
+    }
+
+    #[test]
+    fn broken_module_fails() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/broken".to_owned(), "./tests/broken.js".to_owned())]);
+
+        let bundler = WebBundler::new(&targets, "tests/dist", BundleProfile::Client).unwrap();
+        let err = bundler.exec().unwrap_err();
+        let err = err.downcast_ref::<BundlingError>().unwrap();
+        assert!(!err.errors.is_empty());
+        assert_eq!(err.errors[0].module.as_deref(), Some("./tests/broken.js"));
+        clean();
+    }
+
+    #[test]
+    fn config_hooks_customize_bundles() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/home".to_owned(), "./tests/home.js".to_owned())]);
+
+        let output = WebBundler::new(&targets, "tests/dist", BundleProfile::Client)
+            .unwrap()
+            .config_hook("tests/hooks/naming.bundler.js")
+            .exec()
+            .unwrap();
+        let files = &output.entrypoints["pages/home"].files;
+        assert_eq!(files.last().unwrap(), "pages/home.bundle.js");
+        assert!(Path::new("tests/dist/pages/home.bundle.js").exists());
+        clean();
+    }
+
+    #[test]
+    fn failing_config_hooks_are_reported() {
+        clean();
+        let _metacall = switch::initialize().unwrap();
+        let targets = HashMap::from([("pages/home".to_owned(), "./tests/home.js".to_owned())]);
+
+        let err = WebBundler::new(&targets, "tests/dist", BundleProfile::Client)
+            .unwrap()
+            .config_hook("tests/hooks/throwing.bundler.js")
+            .exec()
+            .unwrap_err();
+        let err = err.downcast_ref::<BundlingError>().unwrap();
+        assert_eq!(err.errors.len(), 1);
+        let diagnostic = &err.errors[0];
+        assert_eq!(
+            diagnostic.module.as_deref(),
+            Some("./tests/hooks/throwing.bundler.js")
+        );
+        assert_eq!(diagnostic.line, Some(3));
+        assert_eq!(
+            diagnostic.message,
+            "Invalid bundler configuration: No configuration for you"
+        );
+        clean();
+    }
+
+    #[test]
+    fn cache_settings() {
+        let targets = HashMap::from([("pages/home".to_owned(), "./tests/home.js".to_owned())]);
+        let bundler = |options: BundleOptions| {
+            WebBundler::new(&targets, "tests/dist", BundleProfile::Client)
+                .unwrap()
+                .options(options)
+                .cache("tests/dist/cache/rspack")
+                .cache_settings()
+        };
+
+        let production = bundler(BundleOptions::default()).unwrap();
+        assert_eq!(production.directory, Path::new("tests/dist/cache/rspack"));
+        assert_ne!(
+            Some(production),
+            bundler(BundleOptions::new(BuildMode::Development, None))
+        );
+        assert_eq!(bundler(BundleOptions::default().cache(false)), None);
+
+        // Bundles built for other browsers don't share their cache
+        let targeted = WebBundler::new(&targets, "tests/dist", BundleProfile::Client)
+            .unwrap()
+            .cache("tests/dist/cache/rspack")
+            .browser_targets(Targets {
+                browsers: vec!["chrome >= 61".to_owned()],
+                polyfills: true,
+            })
+            .cache_settings();
+        assert_ne!(targeted, bundler(BundleOptions::default()));
+    }
+
+    #[test]
+    fn public_paths() {
+        assert_eq!(default_public_path(Path::new("dist")), "/dist/");
+        assert_eq!(default_public_path(Path::new("app/build")), "/build/");
+
+        let targets = HashMap::from([("pages/home".to_owned(), "./tests/home.js".to_owned())]);
+        let bundler = WebBundler::new(&targets, "tests/dist/cache", BundleProfile::Server).unwrap();
+        assert_eq!(bundler.public_path, "/cache/");
+        assert_eq!(bundler.public_path("/dist/").public_path, "/dist/");
+    }
+
+    /// A bundler recording the compilations of each of its runs.
+    #[derive(Debug, Default)]
+    struct Recorder(std::sync::Mutex<Vec<Vec<String>>>);
+
+    impl Bundler for Recorder {
+        fn bundle(&self, compilations: &[Compilation]) -> Result<backends::BundleFuture> {
+            let names: Vec<String> = compilations.iter().map(|c| c.name.to_owned()).collect();
+            self.0.lock().unwrap().push(names.clone());
+            Ok(Box::pin(async move {
+                Ok(names
+                    .into_iter()
+                    .map(|name| (name, BundleOutput::default()))
+                    .collect())
+            }))
+        }
+    }
+
+    #[test]
+    fn custom_backends() {
+        let targets = HashMap::from([("pages/home".to_owned(), "./tests/home.js".to_owned())]);
+        let recorder = Arc::new(Recorder::default());
+        let bundler = || {
+            WebBundler::new(&targets, "tests/dist", BundleProfile::Client)
+                .unwrap()
+                .backend(recorder.clone())
+        };
+
+        bundler().exec().unwrap();
+        let outputs = MultiBundler::new()
+            .add("client", bundler())
+            .add("server", bundler())
+            .add("other", bundler().backend(Arc::new(Recorder::default())))
+            .exec()
+            .unwrap();
+
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![vec![SINGLE_COMPILATION], vec!["client", "server"]]
+        );
+    }
+
     #[test]
     fn invalid_target_fails() {
         clean();
         let _metacall = switch::initialize().unwrap();
         let targets = HashMap::from([("invalid_path.tsx".to_owned(), "invalid_path".to_owned())]);
 
-        let bundler = WebBundler::new(&targets, "tests/dist");
+        let bundler = WebBundler::new(&targets, "tests/dist", BundleProfile::Client);
         assert!(bundler.is_err());
     }
 }
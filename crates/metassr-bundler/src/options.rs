@@ -0,0 +1,177 @@
+use crate::backends::{Bundler, Esbuild, Rspack};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// How a project is built: fast and debuggable, or small.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildMode {
+    /// Minified bundles, with unused code eliminated and `process.env.NODE_ENV` set to `production`.
+    #[default]
+    Production,
+    /// Readable bundles, built as fast as possible with `process.env.NODE_ENV` set to `development`.
+    Development,
+}
+
+impl BuildMode {
+    /// The source maps emitted when none are asked for explicitly.
+    pub fn default_source_maps(&self) -> SourceMaps {
+        match self {
+            Self::Production => SourceMaps::Hidden,
+            Self::Development => SourceMaps::Full,
+        }
+    }
+}
+
+impl Display for BuildMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Production => "production",
+            Self::Development => "development",
+        })
+    }
+}
+
+/// Which source maps are emitted along with the client bundles.
+///
+/// The server bundles always get full source maps (unless they're disabled), as render errors
+/// are mapped back to the project's files through them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceMaps {
+    /// No source maps.
+    None,
+    /// Source maps are emitted, but the bundles don't reference them, so browsers don't load them.
+    Hidden,
+    /// Source maps are emitted and referenced by the bundles.
+    Full,
+}
+
+impl Display for SourceMaps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::None => "none",
+            Self::Hidden => "hidden",
+            Self::Full => "full",
+        })
+    }
+}
+
+/// The bundler backend the bundles are built with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// rspack, with shared chunks, code splitting and a persistent cache.
+    #[default]
+    Rspack,
+    /// esbuild, which builds faster without these features.
+    Esbuild,
+}
+
+impl Backend {
+    /// The bundler of the backend.
+    pub fn bundler(&self) -> &'static dyn Bundler {
+        match self {
+            Self::Rspack => &Rspack,
+            Self::Esbuild => &Esbuild,
+        }
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Rspack => "rspack",
+            Self::Esbuild => "esbuild",
+        })
+    }
+}
+
+/// The browsers the client bundles run in. Server bundles always target the Node version
+/// running MetaSSR.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Targets {
+    /// [browserslist](https://github.com/browserslist/browserslist) queries, e.g. `> 0.5%` or
+    /// `chrome >= 61`. The syntax is left as is when there are none.
+    pub browsers: Vec<String>,
+    /// Whether the core-js polyfills of the features the browsers lack are injected where
+    /// they're used. Requires `core-js` in the project's dependencies.
+    pub polyfills: bool,
+}
+
+impl Display for Targets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.browsers.is_empty() {
+            true => f.write_str("default browsers")?,
+            false => f.write_str(&self.browsers.join(", "))?,
+        }
+        if self.polyfills {
+            f.write_str(" with polyfills")?;
+        }
+        Ok(())
+    }
+}
+
+/// Options shared by every bundle of a build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BundleOptions {
+    pub mode: BuildMode,
+    /// Overrides the source maps of the mode.
+    pub source_maps: Option<SourceMaps>,
+    /// Whether rspack's persistent cache is used, for bundlers given a cache directory.
+    pub cache: bool,
+    pub backend: Backend,
+}
+
+impl BundleOptions {
+    pub fn new(mode: BuildMode, source_maps: Option<SourceMaps>) -> Self {
+        Self {
+            mode,
+            source_maps,
+            cache: true,
+            backend: Backend::default(),
+        }
+    }
+
+    /// Sets the bundler backend, rspack by default.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Enables or disables the persistent cache, enabled by default.
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// The source maps emitted with these options.
+    pub fn source_maps(&self) -> SourceMaps {
+        self.source_maps
+            .unwrap_or_else(|| self.mode.default_source_maps())
+    }
+}
+
+impl Default for BundleOptions {
+    fn default() -> Self {
+        Self::new(BuildMode::default(), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_maps_of_modes() {
+        assert_eq!(BundleOptions::default().source_maps(), SourceMaps::Hidden);
+        assert_eq!(
+            BundleOptions::new(BuildMode::Development, None).source_maps(),
+            SourceMaps::Full
+        );
+        assert_eq!(
+            BundleOptions::new(BuildMode::Production, Some(SourceMaps::None)).source_maps(),
+            SourceMaps::None
+        );
+    }
+}
@@ -0,0 +1,5 @@
+import greeting from 'metassr/greeting';
+
+export default function Greeting() {
+	return <p>{greeting}</p>;
+}
@@ -0,0 +1,4 @@
+// Names the bundles after their compilation instead of their content hash.
+module.exports = (config, { name }) => {
+    config.output.filename = `[name].${name}.js`;
+};
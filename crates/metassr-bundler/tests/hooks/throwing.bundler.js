@@ -0,0 +1,4 @@
+// Fails on every compilation.
+module.exports = () => {
+    throw new Error('No configuration for you');
+};
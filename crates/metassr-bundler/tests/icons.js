@@ -0,0 +1,11 @@
+import small from './small.svg';
+import large from './large.svg';
+
+export default function Icons() {
+	return (
+		<div>
+			<img src={small} />
+			<img src={large} />
+		</div>
+	);
+}
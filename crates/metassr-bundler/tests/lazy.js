@@ -0,0 +1,3 @@
+export default function loadHome() {
+	return import('./home.js');
+}
@@ -0,0 +1,3 @@
+const greet = (name = 'world') => `Hello, ${name}!`;
+
+document.body.textContent = greet();
@@ -0,0 +1,5 @@
+import styles from './card.module.css';
+
+export default function Styled() {
+	return <div className={styles.card}>Styled</div>;
+}
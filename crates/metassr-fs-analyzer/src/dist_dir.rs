@@ -2,13 +2,118 @@ use super::DirectoryAnalyzer;
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     ffi::OsStr,
+    fs,
     marker::Sized,
     path::{Path, PathBuf},
 };
 use walkdir::WalkDir;
 
+/// The length of the content hashes in the bundled filenames, e.g. `index.js.1a2b3c4d.js`.
+pub const CONTENT_HASH_LEN: usize = 8;
+
+/// Returns the logical path of a bundled file, without its content hash.
+///
+/// # Example
+///
+/// ```
+/// use metassr_fs_analyzer::dist_dir::logical_path;
+/// use std::path::{Path, PathBuf};
+///
+/// assert_eq!(
+///     logical_path(Path::new("dist/pages/index.js.1a2b3c4d.js")),
+///     PathBuf::from("dist/pages/index.js.js")
+/// );
+/// assert_eq!(logical_path(Path::new("dist/pages/index.js.js")), PathBuf::from("dist/pages/index.js.js"));
+/// ```
+pub fn logical_path(path: &Path) -> PathBuf {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return path.to_path_buf();
+    };
+
+    let mut parts: Vec<&str> = name.split('.').collect();
+    let is_hash =
+        |part: &str| part.len() == CONTENT_HASH_LEN && part.chars().all(|c| c.is_ascii_hexdigit());
+    if parts.len() > 2 && is_hash(parts[parts.len() - 2]) {
+        parts.remove(parts.len() - 2);
+    }
+    path.with_file_name(parts.join("."))
+}
+
+/// The name of the client manifest, in the root of the `dist/` directory.
+pub const CLIENT_MANIFEST: &str = "client-manifest.json";
+
+/// The files bundled for a page's hydration script, relative to the `dist/` directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientEntry {
+    /// The files loaded with the page, in dependency order (shared chunks first).
+    pub files: Vec<PathBuf>,
+    /// The chunks the page loads on demand through dynamic `import()`.
+    #[serde(default)]
+    pub chunks: Vec<PathBuf>,
+    /// The images and fonts under `assets/` that the page's files reference.
+    #[serde(default)]
+    pub assets: Vec<PathBuf>,
+}
+
+/// `ClientManifest` records the files the client build emitted for each page, keyed by
+/// bundler entry (e.g. `pages/blog/index.js`). Pages share the `vendor` and `common` chunks,
+/// so their files can't be found by walking each page's directory.
+///
+/// # Example
+///
+/// ```no_run
+/// use metassr_fs_analyzer::dist_dir::ClientManifest;
+///
+/// let manifest = ClientManifest::read("dist").unwrap().unwrap_or_default();
+/// for (entry, files) in manifest.entries {
+///     println!("{entry}: {:?}", files.files);
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientManifest {
+    pub entries: BTreeMap<String, ClientEntry>,
+}
+
+impl ClientManifest {
+    /// Reads the client manifest of a `dist/` directory, if the client side was built.
+    pub fn read<S: AsRef<Path> + ?Sized>(dist: &S) -> Result<Option<Self>> {
+        let path = dist.as_ref().join(CLIENT_MANIFEST);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    /// Writes the client manifest to the root of a `dist/` directory.
+    pub fn write<S: AsRef<Path> + ?Sized>(&self, dist: &S) -> Result<()> {
+        fs::write(
+            dist.as_ref().join(CLIENT_MANIFEST),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// Returns every file referenced by the entries, relative to the `dist/` directory.
+    pub fn files(&self) -> HashSet<&PathBuf> {
+        self.entries
+            .values()
+            .flat_map(|entry| entry.files.iter().chain(&entry.chunks))
+            .collect()
+    }
+}
+
+/// Returns the route of a bundler entry, as used by `DistDirContainer` (e.g. `blog` for
+/// `pages/blog/index.js`, and `#root` for `pages/index.js`).
+fn entry_route(entry: &str) -> String {
+    let dir = Path::new(entry).parent().unwrap_or(Path::new(""));
+    match dir.strip_prefix("pages").unwrap_or(dir) {
+        p if p == Path::new("") => "#root".to_owned(),
+        p => p.to_str().unwrap().to_owned(),
+    }
+}
+
 /// `DistDirContainer` is a structure that holds the analyzing results for the `dist/` directory.
 /// It contains a `HashMap` where the keys are page names (directories) and the values are `PageEntry` structures.
 ///
@@ -16,10 +121,14 @@ use walkdir::WalkDir;
 ///
 /// ```no_run
 /// use metassr_fs_analyzer::dist_dir::{DistDirContainer, PageEntry};
-/// use std::{collections::HashMap, path::PathBuf};
+/// use std::{
+///     collections::{BTreeMap, HashMap},
+///     path::PathBuf,
+/// };
 ///
 /// let mut container = DistDirContainer {
 ///     pages: HashMap::new(),
+///     assets: BTreeMap::new(),
 /// };
 ///
 /// let page_entry = PageEntry::new(PathBuf::from("/dist/pages/home.js"));
@@ -30,10 +139,19 @@ use walkdir::WalkDir;
 #[derive(Debug)]
 pub struct DistDirContainer {
     pub pages: HashMap<String, PageEntry>, // Maps page paths to page entries
+    pub assets: BTreeMap<PathBuf, PathBuf>, // Logical paths of the files under `assets/` mapped to their hashed paths
+}
+
+impl DistDirContainer {
+    /// Returns the hashed path of an asset given its logical path (e.g. `dist/assets/logo.png`).
+    pub fn asset(&self, logical: &Path) -> Option<&PathBuf> {
+        self.assets.get(logical)
+    }
 }
 
 /// `PageEntry` represents the details for each page found in the `dist/` directory.
-/// It includes the paths for JavaScript and CSS files (scripts and styles).
+/// It includes the paths for JavaScript and CSS files (scripts and styles), which are
+/// content-hashed, and the mapping from their logical paths to the hashed ones.
 ///
 /// # Example
 ///
@@ -53,6 +171,12 @@ pub struct PageEntry {
     pub scripts: Vec<PathBuf>, // List of paths to JavaScript files for the page
     pub styles: Vec<PathBuf>,  // List of paths to CSS files for the page
     pub path: PathBuf,         // The actual path of the page (directory)
+    #[serde(default)]
+    pub files: BTreeMap<PathBuf, PathBuf>, // Logical paths of the files mapped to their hashed paths
+    #[serde(default)]
+    pub chunks: Vec<PathBuf>, // Chunks loaded on demand through dynamic `import()`
+    #[serde(default)]
+    pub assets: Vec<PathBuf>, // Images and fonts referenced by the scripts and styles
 }
 
 impl PageEntry {
@@ -72,6 +196,9 @@ impl PageEntry {
             scripts: vec![], // Initialize with an empty list of scripts
             styles: vec![],  // Initialize with an empty list of styles
             path,            // Set the path of the page
+            files: BTreeMap::new(),
+            chunks: vec![],
+            assets: vec![],
         }
     }
 
@@ -89,6 +216,7 @@ impl PageEntry {
     /// ```
     pub fn push_script(&mut self, path: &Path) {
         self.scripts.push(path.to_path_buf());
+        self.files.insert(logical_path(path), path.to_path_buf());
     }
 
     /// Adds a style (CSS file) to the `styles` list for the page.
@@ -105,6 +233,29 @@ impl PageEntry {
     /// ```
     pub fn push_style(&mut self, path: &Path) {
         self.styles.push(path.to_path_buf());
+        self.files.insert(logical_path(path), path.to_path_buf());
+    }
+
+    /// Adds an asset (image or font file) to the `assets` list for the page.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use metassr_fs_analyzer::dist_dir::PageEntry;
+    /// use std::path::PathBuf;
+    ///
+    /// let mut page_entry = PageEntry::new(PathBuf::from("/dist/pages/home"));
+    /// page_entry.push_asset(&PathBuf::from("/dist/assets/inter.1a2b3c4d.woff2"));
+    /// println!("{:?}", page_entry.assets);
+    /// ```
+    pub fn push_asset(&mut self, path: &Path) {
+        self.assets.push(path.to_path_buf());
+        self.files.insert(logical_path(path), path.to_path_buf());
+    }
+
+    /// Returns the hashed path of a file given its logical path (e.g. `dist/pages/index.js.js`).
+    pub fn resolve(&self, logical: &Path) -> Option<&PathBuf> {
+        self.files.get(logical)
     }
 }
 
@@ -155,6 +306,47 @@ impl DistDir {
 
         Ok(Self(path))
     }
+
+    /// Builds the page entries from the client manifest, with the files of each page in the
+    /// order they have to be loaded.
+    fn pages_from(&self, manifest: ClientManifest) -> HashMap<String, PageEntry> {
+        let mut pages = HashMap::new();
+        for (entry, client_entry) in manifest.entries {
+            let path = self.0.join(&entry);
+            let dir = path.parent().unwrap_or(&self.0);
+            let mut page_entry = PageEntry::new(dir.canonicalize().unwrap_or(dir.to_path_buf()));
+
+            for file in client_entry.files {
+                let file = self.0.join(file);
+                match file.extension().and_then(|ext| ext.to_str()) {
+                    Some("js") => page_entry.push_script(&file),
+                    Some("css") => page_entry.push_style(&file),
+                    _ => (),
+                }
+            }
+            page_entry.chunks = client_entry
+                .chunks
+                .iter()
+                .map(|chunk| self.0.join(chunk))
+                .collect();
+            for asset in client_entry.assets {
+                page_entry.push_asset(&self.0.join(asset));
+            }
+
+            pages.insert(entry_route(&entry), page_entry);
+        }
+        pages
+    }
+
+    /// Indexes the files under `assets/`, by their logical paths.
+    fn assets(&self) -> BTreeMap<PathBuf, PathBuf> {
+        WalkDir::new(self.0.join("assets"))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file() && e.path().extension() != Some(OsStr::new("map")))
+            .map(|e| (logical_path(e.path()), e.path().to_path_buf()))
+            .collect()
+    }
 }
 
 /// `AnalyzeDir` trait is implemented for `DistDir`.
@@ -174,8 +366,9 @@ impl DistDir {
 impl DirectoryAnalyzer for DistDir {
     type Output = DistDirContainer; // The output of the analysis is a `DistDirContainer`
 
-    /// Analyzes the `dist/` directory to find and organize JavaScript and CSS files
-    /// inside the `pages/` subdirectory, if it exists.
+    /// Analyzes the `dist/` directory to find and organize JavaScript and CSS files of each page.
+    /// They're read from the client manifest when it exists, or found inside the `pages/`
+    /// subdirectory otherwise.
     ///
     /// # Example
     ///
@@ -190,6 +383,13 @@ impl DirectoryAnalyzer for DistDir {
     /// }
     /// ```
     fn analyze(&self) -> Result<Self::Output> {
+        if let Some(manifest) = ClientManifest::read(&self.0)? {
+            return Ok(Self::Output {
+                pages: self.pages_from(manifest),
+                assets: self.assets(),
+            });
+        }
+
         let pages_path = self.0.join("pages"); // Define the path to the `pages` directory inside `dist`
         let mut pages: HashMap<String, PageEntry> = HashMap::new(); // Create a `HashMap` to store pages
 
@@ -238,8 +438,11 @@ impl DirectoryAnalyzer for DistDir {
             }
         }
 
-        // Return the analyzed pages in a `DistDirContainer`
-        Ok(Self::Output { pages })
+        // Return the analyzed pages and assets in a `DistDirContainer`
+        Ok(Self::Output {
+            pages,
+            assets: self.assets(),
+        })
     }
 }
 
@@ -324,6 +527,131 @@ mod tests {
         cleanup_test_dist_dir(test_dir);
     }
 
+    #[test]
+    fn test_hashed_files() {
+        let test_dir = std::env::temp_dir()
+            .join(Rand::new().val().to_string())
+            .join("hashed-test-dist");
+        let pages_dir = test_dir.join("pages");
+
+        fs::create_dir_all(&pages_dir).unwrap();
+        fs::write(pages_dir.join("index.js.1a2b3c4d.js"), "// JavaScript file").unwrap();
+        fs::write(pages_dir.join("index.js.5e6f7a8b.css"), "/* CSS file */").unwrap();
+
+        let result = DistDir::new(&test_dir).unwrap().analyze().unwrap();
+        let root_page = result.pages.get("#root").expect("Root page should exist");
+
+        assert_eq!(
+            root_page.resolve(&pages_dir.join("index.js.js")),
+            Some(&pages_dir.join("index.js.1a2b3c4d.js"))
+        );
+        assert_eq!(
+            root_page.resolve(&pages_dir.join("index.js.css")),
+            Some(&pages_dir.join("index.js.5e6f7a8b.css"))
+        );
+
+        cleanup_test_dist_dir(test_dir);
+    }
+
+    #[test]
+    fn test_client_manifest() {
+        let test_dir = std::env::temp_dir()
+            .join(Rand::new().val().to_string())
+            .join("manifest-test-dist");
+        fs::create_dir_all(test_dir.join("pages/blog")).unwrap();
+
+        let manifest = ClientManifest {
+            entries: BTreeMap::from([
+                (
+                    "pages/index.js".to_string(),
+                    ClientEntry {
+                        files: vec![
+                            "chunks/vendor.1a2b3c4d.js".into(),
+                            "pages/index.js.5e6f7a8b.css".into(),
+                            "pages/index.js.5e6f7a8b.js".into(),
+                        ],
+                        chunks: vec!["chunks/chart.9c0d1e2f.js".into()],
+                        assets: vec!["assets/inter.7a8b9c0d.woff2".into()],
+                    },
+                ),
+                (
+                    "pages/blog/index.js".to_string(),
+                    ClientEntry {
+                        files: vec![
+                            "chunks/vendor.1a2b3c4d.js".into(),
+                            "pages/blog/index.js.3a4b5c6d.js".into(),
+                        ],
+                        chunks: vec![],
+                        assets: vec![],
+                    },
+                ),
+            ]),
+        };
+        manifest.write(&test_dir).unwrap();
+        assert_eq!(
+            ClientManifest::read(&test_dir).unwrap(),
+            Some(manifest.clone())
+        );
+        assert_eq!(manifest.files().len(), 5);
+
+        let result = DistDir::new(&test_dir).unwrap().analyze().unwrap();
+        let root_page = result.pages.get("#root").expect("Root page should exist");
+        assert_eq!(
+            root_page.scripts,
+            vec![
+                test_dir.join("chunks/vendor.1a2b3c4d.js"),
+                test_dir.join("pages/index.js.5e6f7a8b.js")
+            ]
+        );
+        assert_eq!(
+            root_page.styles,
+            vec![test_dir.join("pages/index.js.5e6f7a8b.css")]
+        );
+        assert_eq!(
+            root_page.chunks,
+            vec![test_dir.join("chunks/chart.9c0d1e2f.js")]
+        );
+        assert_eq!(
+            root_page.assets,
+            vec![test_dir.join("assets/inter.7a8b9c0d.woff2")]
+        );
+
+        let blog_page = result.pages.get("blog").expect("Blog page should exist");
+        assert_eq!(blog_page.scripts.len(), 2);
+        assert_eq!(
+            blog_page.path,
+            test_dir.join("pages/blog").canonicalize().unwrap()
+        );
+
+        cleanup_test_dist_dir(test_dir);
+    }
+
+    #[test]
+    fn test_assets_index() {
+        let test_dir = std::env::temp_dir()
+            .join(Rand::new().val().to_string())
+            .join("assets-test-dist");
+        let assets_dir = test_dir.join("assets");
+
+        fs::create_dir_all(test_dir.join("pages")).unwrap();
+        fs::create_dir_all(&assets_dir).unwrap();
+        fs::write(assets_dir.join("logo.3f2a9c1e.png"), "").unwrap();
+        fs::write(assets_dir.join("inter.7a8b9c0d.woff2"), "").unwrap();
+
+        let result = DistDir::new(&test_dir).unwrap().analyze().unwrap();
+        assert_eq!(result.assets.len(), 2);
+        assert_eq!(
+            result.asset(&assets_dir.join("logo.png")),
+            Some(&assets_dir.join("logo.3f2a9c1e.png"))
+        );
+        assert_eq!(
+            result.asset(&assets_dir.join("inter.woff2")),
+            Some(&assets_dir.join("inter.7a8b9c0d.woff2"))
+        );
+
+        cleanup_test_dist_dir(test_dir);
+    }
+
     #[test]
     fn test_dist_dir_not_found() {
         let invalid_path = std::env::temp_dir().join("invalid-dist");
@@ -1,6 +1,11 @@
 use super::DirectoryAnalyzer;
 use anyhow::{anyhow, Result};
-use std::{collections::HashMap, ffi::OsStr, marker::Sized, path::PathBuf};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    marker::Sized,
+    path::{Path, PathBuf},
+};
 use walkdir::WalkDir;
 
 /// Wrappers for special entries that collected by the source analyzer
@@ -14,29 +19,53 @@ pub mod special_entries {
     /// Represents a special entry for the `_head.[js, jsx, ts, tsx]` file.
     #[derive(Debug, Clone)]
     pub struct Head(pub PathBuf);
+
+    /// Represents a special entry for the optional `_document.[html, js, jsx, ts, tsx]` file,
+    /// the HTML document shell that pages are rendered into.
+    #[derive(Debug, Clone)]
+    pub struct Document(pub PathBuf);
 }
 
 pub type PagesEntriesType = HashMap<String, PathBuf>;
-pub type SpecialEntriesType = (Option<special_entries::App>, Option<special_entries::Head>);
+/// Maps a directory under `pages/` (`""` for `pages/` itself) to its `_layout` file.
+pub type LayoutsEntriesType = HashMap<String, PathBuf>;
+pub type SpecialEntriesType = (
+    Option<special_entries::App>,
+    Option<special_entries::Head>,
+    Option<special_entries::Document>,
+);
+
+/// Extensions of the script files that can be pages or special entries.
+const SCRIPT_EXTENSIONS: [&str; 4] = ["js", "jsx", "tsx", "ts"];
 
 /// A container holding the results of analyzing a source directory.
 ///
-/// This struct holds the pages and special entries found in the source directory.
+/// This struct holds the pages, layouts and special entries found in the source directory.
 #[derive(Debug, Clone)]
 pub struct SourceDirContainer {
     pub pages: PagesEntriesType,
+    pub layouts: LayoutsEntriesType,
     pub specials: SpecialEntriesType,
 }
 
 impl SourceDirContainer {
-    /// Creates a new `SourceDirContainer` with the given pages and special entries.
+    /// Creates a new `SourceDirContainer` with the given pages, layouts and special entries.
     ///
     /// **Parameters**
     ///
     /// - `pages`: A `HashMap` where keys are routes and values are paths to page files.
-    /// - `specials`: A tuple containing optional special entries (`App` and `Head`).
-    pub fn new(pages: PagesEntriesType, specials: SpecialEntriesType) -> Self {
-        Self { pages, specials }
+    /// - `layouts`: A `HashMap` where keys are directories under `pages/` and values are paths to `_layout` files.
+    /// - `specials`: A tuple containing optional special entries (`App`, `Head` and `Document`).
+    pub fn new(
+        pages: PagesEntriesType,
+        layouts: LayoutsEntriesType,
+        specials: SpecialEntriesType,
+    ) -> Self {
+        Self {
+            pages,
+            layouts,
+            specials,
+        }
     }
 
     /// Retrieves the special entries from the container.
@@ -46,7 +75,7 @@ impl SourceDirContainer {
     /// Returns a `Result` containing a tuple of `App` and `Head` if both are present,
     /// or an error if one or both are missing.
     pub fn specials(&self) -> Result<(special_entries::App, special_entries::Head)> {
-        let (app, head) = self.specials.clone();
+        let (app, head, _) = self.specials.clone();
         if let (Some(app), Some(head)) = (app.clone(), head.clone()) {
             return Ok((app, head));
         }
@@ -63,6 +92,15 @@ impl SourceDirContainer {
         ))
     }
 
+    /// Retrieves the custom document entry, if the project has one.
+    ///
+    /// **Returns**
+    ///
+    /// Returns `Some(Document)` if a `_document` file was found, or `None` to use the default document.
+    pub fn document(&self) -> Option<special_entries::Document> {
+        self.specials.2.clone()
+    }
+
     /// Retrieves the pages entries from the container.
     ///
     /// **Returns**
@@ -71,6 +109,26 @@ impl SourceDirContainer {
     pub fn pages(&self) -> PagesEntriesType {
         self.pages.clone()
     }
+
+    /// Retrieves the layouts wrapping a page, from the `_layout` files of its ancestor directories.
+    ///
+    /// **Parameters**
+    ///
+    /// - `page`: The page route, as a key of [`SourceDirContainer::pages`] (e.g. `blog/$article.tsx`).
+    ///
+    /// **Returns**
+    ///
+    /// Returns the paths to the layout files, ordered from the outermost to the innermost.
+    pub fn layouts_for(&self, page: &str) -> Vec<PathBuf> {
+        let dir = Path::new(page).parent().unwrap_or(Path::new(""));
+        let mut ancestors: Vec<&Path> = dir.ancestors().collect();
+        ancestors.reverse();
+
+        ancestors
+            .iter()
+            .filter_map(|ancestor| self.layouts.get(ancestor.to_str().unwrap()).cloned())
+            .collect()
+    }
 }
 
 /// A directory analyzer for a source directory.
@@ -104,46 +162,50 @@ impl DirectoryAnalyzer for SourceDir {
     fn analyze(&self) -> Result<Self::Output> {
         let src = self.0.to_str().unwrap();
 
-        let list_of_specials = ["_app", "_head"];
+        let list_of_specials = ["_app", "_head", "_document"];
         let mut pages: HashMap<String, PathBuf> = HashMap::new();
-        let mut specials: SpecialEntriesType = (None, None);
-
-        for entry in WalkDir::new(src)
-            .into_iter()
-            .filter_map(|e| match e.ok() {
-                Some(e) if e.path().is_file() => Some(e),
-                _ => None,
-            })
-            .skip_while(|e| {
-                // Check if the entry is a js/ts file.
-                let exts: Vec<&str> = vec!["js", "jsx", "tsx", "ts"];
-                !exts.contains(&e.path().extension().unwrap().to_str().unwrap())
-            })
-        {
+        let mut layouts: LayoutsEntriesType = HashMap::new();
+        let mut specials: SpecialEntriesType = (None, None, None);
+
+        for entry in WalkDir::new(src).into_iter().filter_map(|e| match e.ok() {
+            Some(e) if e.path().is_file() => Some(e),
+            _ => None,
+        }) {
             let path = entry.path();
             let stem = path.file_stem().unwrap().to_str().unwrap();
             let stripped = path.strip_prefix(src)?;
 
+            // Only js/ts files can be pages or special entries, except the document which can be plain html.
+            let ext = path.extension().and_then(OsStr::to_str).unwrap_or_default();
+            let is_html_document = stem == "_document" && ext == "html";
+            if !(SCRIPT_EXTENSIONS.contains(&ext) || is_html_document) {
+                continue;
+            }
+
             match stripped.iter().next() {
                 Some(_) if list_of_specials.contains(&stem) => match stem {
                     "_app" => specials.0 = Some(special_entries::App(path.to_path_buf())),
                     "_head" => specials.1 = Some(special_entries::Head(path.to_path_buf())),
+                    "_document" => specials.2 = Some(special_entries::Document(path.to_path_buf())),
                     _ => (),
                 },
 
                 Some(p) if p == OsStr::new("pages") => {
-                    let route = path
-                        .strip_prefix([src, "/pages"].concat())?
-                        .to_str()
-                        .unwrap();
-                    pages.insert(route.to_owned(), path.to_path_buf());
+                    let route = path.strip_prefix([src, "/pages"].concat())?;
+
+                    if stem == "_layout" {
+                        let dir = route.parent().unwrap().to_str().unwrap();
+                        layouts.insert(dir.to_owned(), path.to_path_buf());
+                    } else {
+                        pages.insert(route.to_str().unwrap().to_owned(), path.to_path_buf());
+                    }
                 }
 
                 _ => (),
             }
         }
 
-        let container = SourceDirContainer::new(pages, specials);
+        let container = SourceDirContainer::new(pages, layouts, specials);
 
         // Return an error if specials not found.
         if let Err(err) = container.specials() {
@@ -189,6 +251,7 @@ mod tests {
         let result = source_dir.analyze().unwrap();
         assert_eq!(result.pages().len(), pages.len());
         assert!(result.specials().is_ok());
+        assert!(result.document().is_none());
 
         // Cleanup
         for page in pages.iter() {
@@ -220,4 +283,71 @@ mod tests {
         fs::remove_file(&page_path).unwrap();
         fs::remove_dir_all(source_dir.0).unwrap();
     }
+
+    /// Test case to verify that a custom html document is picked up, and isn't treated as a page.
+    #[test]
+    fn test_custom_document() {
+        let source_dir = create_temp_source_dir().unwrap();
+        let files = [
+            "_app.jsx",
+            "_head.jsx",
+            "_document.html",
+            "pages/index.jsx",
+            "pages/notes.html",
+        ];
+
+        for file in files.iter() {
+            let path = source_dir.0.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, b"dummy content").unwrap();
+        }
+
+        let result = source_dir.analyze().unwrap();
+        let document = result.document().expect("Document should be found");
+        assert_eq!(document.0, source_dir.0.join("_document.html"));
+        assert_eq!(result.pages().len(), 1, "Only script files should be pages");
+
+        // Cleanup
+        fs::remove_dir_all(source_dir.0).unwrap();
+    }
+
+    /// Test case to verify that layouts are discovered at any level, and ordered from the outermost.
+    #[test]
+    fn test_nested_layouts() {
+        let source_dir = create_temp_source_dir().unwrap();
+        let files = [
+            "_app.jsx",
+            "_head.jsx",
+            "pages/_layout.jsx",
+            "pages/index.jsx",
+            "pages/blog/_layout.tsx",
+            "pages/blog/index.tsx",
+            "pages/blog/posts/$post.tsx",
+            "pages/about/index.jsx",
+        ];
+
+        for file in files.iter() {
+            let path = source_dir.0.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, b"dummy content").unwrap();
+        }
+
+        let result = source_dir.analyze().unwrap();
+        let root_layout = source_dir.0.join("pages/_layout.jsx");
+        let blog_layout = source_dir.0.join("pages/blog/_layout.tsx");
+
+        assert_eq!(result.pages().len(), 4, "Layouts shouldn't be pages");
+        assert_eq!(result.layouts_for("index.jsx"), vec![root_layout.clone()]);
+        assert_eq!(
+            result.layouts_for("about/index.jsx"),
+            vec![root_layout.clone()]
+        );
+        assert_eq!(
+            result.layouts_for("blog/posts/$post.tsx"),
+            vec![root_layout, blog_layout]
+        );
+
+        // Cleanup
+        fs::remove_dir_all(source_dir.0).unwrap();
+    }
 }
@@ -1,7 +1,8 @@
 use anyhow::Result;
 use axum::{
     extract::{Path, Query},
-    response::Html,
+    http::StatusCode,
+    response::{Html, IntoResponse},
     routing::get,
 };
 use metassr_build::server::renderer::page::PageRenderer;
@@ -10,8 +11,13 @@ use metassr_fs_analyzer::{
     DirectoryAnalyzer,
 };
 use std::{collections::HashMap, fs::read_to_string, path::PathBuf};
+use tracing::error;
 
-use crate::RunningType;
+use crate::{
+    live_reload::{inject_script, LiveReload},
+    overlay::ErrorOverlay,
+    RunningType,
+};
 
 use super::router::RouterMut;
 
@@ -20,6 +26,7 @@ pub struct PagesHandler<'a, S: Clone + Send + Sync + 'static> {
     pub pages: HashMap<String, PageEntry>,
     pub dist_dir: PathBuf,
     pub running_type: RunningType,
+    pub live_reload: Option<LiveReload>,
 }
 
 impl<'a, S: Clone + Send + Sync + 'static> PagesHandler<'a, S> {
@@ -27,15 +34,21 @@ impl<'a, S: Clone + Send + Sync + 'static> PagesHandler<'a, S> {
         app: &'a mut RouterMut<S>,
         dist_dir: &str,
         running_type: RunningType,
+        live_reload: Option<LiveReload>,
     ) -> Result<Self> {
         Ok(Self {
             app,
             pages: DistDir::new(&dist_dir)?.analyze()?.pages,
             dist_dir: PathBuf::from(dist_dir),
             running_type,
+            live_reload,
         })
     }
     pub fn build(&mut self) -> Result<()> {
+        if let Some(live_reload) = &self.live_reload {
+            return self.build_dev(live_reload.clone());
+        }
+
         for (route, entries) in self.pages.iter() {
             let html = match self.running_type {
                 RunningType::SSG => Box::new(read_to_string(entries.path.join("index.html"))?),
@@ -51,15 +64,61 @@ impl<'a, S: Clone + Send + Sync + 'static> PagesHandler<'a, S> {
                     Html(*html)
                 };
 
-            let route = format!(
-                "/{}",
-                match route {
-                    e if e == &String::from("#root") => "".to_string(),
-                    _ => route.replace('$', ":"),
+            self.app.route(&route_path(route), get(handler));
+        }
+        Ok(())
+    }
+
+    /// In development, pages are rendered on every request from the latest build output.
+    /// Build and render errors are shown in an error overlay instead of the page.
+    fn build_dev(&mut self, live_reload: LiveReload) -> Result<()> {
+        for route in self.pages.keys() {
+            let dist_dir = self.dist_dir.clone();
+            let page = route.clone();
+            let live_reload = live_reload.clone();
+
+            let handler = move || async move {
+                if let Some(overlay) = live_reload.build_error() {
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Html(inject_script(&overlay.to_html())),
+                    )
+                        .into_response();
                 }
-            );
-            self.app.route(&route, get(handler));
+
+                let rendered = tokio::task::spawn_blocking(move || {
+                    let _lock = live_reload.lock();
+                    PageRenderer::from_manifest(&dist_dir, &page)?.render()
+                })
+                .await
+                .unwrap_or_else(|e| Err(e.into()));
+
+                match rendered {
+                    Ok(html) => Html(inject_script(&html)).into_response(),
+                    Err(e) => {
+                        error!("Couldn't render page: {e}");
+                        let overlay = ErrorOverlay::new("Render failed", &e);
+                        (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            Html(inject_script(&overlay.to_html())),
+                        )
+                            .into_response()
+                    }
+                }
+            };
+
+            self.app.route(&route_path(route), get(handler));
         }
         Ok(())
     }
 }
+
+fn route_path(route: &str) -> String {
+    format!(
+        "/{}",
+        match route {
+            "#root" => "".to_string(),
+            _ => route.replace('$', ":"),
+        }
+    )
+}
@@ -0,0 +1,185 @@
+/// Caching layer for the bundled files of MetaSSR internal server
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::{self, Next},
+    response::Response,
+};
+use metassr_build::images::{ImageManifest, IMAGE_MANIFEST};
+use metassr_bundler::default_public_path;
+use metassr_fs_analyzer::dist_dir::{ClientManifest, CLIENT_MANIFEST};
+use std::{
+    collections::HashSet,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+use tracing::warn;
+
+use super::tracing::LayerSetup;
+use crate::router::RouterMut;
+
+/// The `Cache-Control` header of content-hashed files: a new build emits changed files under
+/// new names, so browsers can keep them for a year without revalidating.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+#[derive(Clone, Copy)]
+pub struct CacheControlLayer;
+
+impl LayerSetup for CacheControlLayer {
+    /// The `dist/` directory, whose manifests list the content-hashed files.
+    type LayerOptions = PathBuf;
+    fn setup<S: Clone + Send + Sync + 'static>(
+        dist_dir: Self::LayerOptions,
+        app: &mut RouterMut<S>,
+    ) {
+        let hashed = Arc::new(HashedFiles::new(dist_dir));
+        app.layer(middleware::from_fn_with_state(hashed, cache_hashed_files));
+    }
+}
+
+/// The URLs of the content-hashed files of a `dist/` directory: the bundles, their stylesheets,
+/// chunks and assets listed by the client manifest, and the image variants listed by the image
+/// manifest. Other files under `/dist` may have any name, so only these are cached for good.
+struct HashedFiles {
+    dist_dir: PathBuf,
+    /// The modification times of the manifests when they were read, and the URLs they listed.
+    /// The manifests are read again once a build rewrites them.
+    urls: Mutex<(Vec<Option<SystemTime>>, HashSet<String>)>,
+}
+
+impl HashedFiles {
+    fn new(dist_dir: PathBuf) -> Self {
+        Self {
+            dist_dir,
+            urls: Mutex::new((vec![], HashSet::new())),
+        }
+    }
+
+    /// Returns whether a request path points to a content-hashed file.
+    fn contains(&self, path: &str) -> bool {
+        let versions = self.versions();
+        let mut urls = self.urls.lock().unwrap();
+        if urls.0 != versions {
+            match self.read() {
+                Ok(read) => *urls = (versions, read),
+                Err(e) => {
+                    warn!(
+                        target = "server",
+                        message = format!("Cannot read the manifests of the hashed files: {e}")
+                    );
+                    return false;
+                }
+            }
+        }
+        urls.1.contains(path)
+    }
+
+    /// The modification times of the manifests, `None` for the missing ones.
+    fn versions(&self) -> Vec<Option<SystemTime>> {
+        [CLIENT_MANIFEST, IMAGE_MANIFEST]
+            .iter()
+            .map(|manifest| {
+                fs::metadata(self.dist_dir.join(manifest))
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .collect()
+    }
+
+    fn read(&self) -> Result<HashSet<String>> {
+        let public_path = default_public_path(&self.dist_dir);
+        let mut urls = HashSet::new();
+        if let Some(manifest) = ClientManifest::read(&self.dist_dir)? {
+            for entry in manifest.entries.values() {
+                for file in entry.files.iter().chain(&entry.chunks).chain(&entry.assets) {
+                    urls.insert(format!("{public_path}{}", file.display()));
+                }
+            }
+        }
+        if let Some(manifest) = ImageManifest::read(&self.dist_dir)? {
+            let variants = manifest
+                .images
+                .into_values()
+                .flat_map(|image| image.variants);
+            urls.extend(variants.map(|variant| variant.src));
+        }
+        Ok(urls)
+    }
+}
+
+async fn cache_hashed_files(
+    State(hashed): State<Arc<HashedFiles>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let hashed = hashed.contains(req.uri().path());
+    let mut res = next.run(req).await;
+    if hashed && res.status().is_success() {
+        res.headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static(IMMUTABLE));
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metassr_build::images::{ImageEntry, ImageFormat, ImageVariant};
+    use metassr_fs_analyzer::dist_dir::ClientEntry;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn hashed_files() {
+        let dist = std::env::temp_dir()
+            .join(format!("metassr-cache-control-{}", std::process::id()))
+            .join("dist");
+        fs::create_dir_all(&dist).unwrap();
+        let hashed = HashedFiles::new(dist.clone());
+        assert!(!hashed.contains("/dist/pages/index.js.1a2b3c4d.js"));
+
+        ClientManifest {
+            entries: BTreeMap::from([(
+                "pages/index.js".to_owned(),
+                ClientEntry {
+                    files: vec![
+                        "chunks/vendor.9a7e3c52.js".into(),
+                        "pages/index.js.1a2b3c4d.js".into(),
+                    ],
+                    chunks: vec![],
+                    assets: vec!["assets/inter.7a8b9c0d.woff2".into()],
+                },
+            )]),
+        }
+        .write(&dist)
+        .unwrap();
+        ImageManifest {
+            images: BTreeMap::from([(
+                "static/images/hero.jpg".to_owned(),
+                ImageEntry {
+                    width: 1920,
+                    height: 1080,
+                    variants: vec![ImageVariant {
+                        src: "/dist/images/hero-640.5e6f7a8b.webp".to_owned(),
+                        width: 640,
+                        format: ImageFormat::Webp,
+                    }],
+                },
+            )]),
+        }
+        .write(&dist)
+        .unwrap();
+
+        // The manifests are read again once they're written
+        assert!(hashed.contains("/dist/pages/index.js.1a2b3c4d.js"));
+        assert!(hashed.contains("/dist/chunks/vendor.9a7e3c52.js"));
+        assert!(hashed.contains("/dist/assets/inter.7a8b9c0d.woff2"));
+        assert!(hashed.contains("/dist/images/hero-640.5e6f7a8b.webp"));
+        assert!(!hashed.contains("/dist/pages/index.js.1a2b3c4d.js.map"));
+        assert!(!hashed.contains("/dist/pages/logo.deadbeef.png"));
+        assert!(!hashed.contains("/dist/client-manifest.json"));
+        fs::remove_dir_all(dist.parent().unwrap()).unwrap();
+    }
+}
@@ -1 +1,2 @@
+pub mod cache_control;
 pub mod tracing;
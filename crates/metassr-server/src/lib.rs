@@ -1,14 +1,22 @@
 mod fallback;
 mod handler;
 mod layers;
+mod live_reload;
+mod overlay;
 mod router;
 
 use fallback::Fallback;
 use handler::PagesHandler;
-use layers::tracing::{LayerSetup, TracingLayer, TracingLayerOptions};
+use layers::{
+    cache_control::CacheControlLayer,
+    tracing::{LayerSetup, TracingLayer, TracingLayerOptions},
+};
+pub use live_reload::LiveReload;
+use live_reload::LIVE_RELOAD_PATH;
 
-use anyhow::Result;
-use axum::{http::StatusCode, response::Redirect, Router};
+use anyhow::{anyhow, Result};
+use axum::{http::StatusCode, response::Redirect, routing::get, Router};
+use metassr_bundler::default_public_path;
 use router::RouterMut;
 use std::path::{Path, PathBuf};
 use tower_http::services::ServeDir;
@@ -24,7 +32,13 @@ pub struct ServerConfigs {
     pub port: u16,
     pub _enable_http_logging: bool,
     pub root_path: PathBuf,
+    /// The build's output directory, relative to the root. Its files are served under the
+    /// URL the build links them with: the directory's name (e.g. `/dist/` for `dist`).
+    pub dist_dir: PathBuf,
     pub running_type: RunningType,
+    /// Enables development mode: pages are rendered on each request, and browsers are
+    /// reloaded after every rebuild.
+    pub live_reload: Option<LiveReload>,
 }
 
 pub struct Server {
@@ -41,16 +55,24 @@ impl Server {
             tokio::net::TcpListener::bind(format!("0.0.0.0:{}", self.configs.port)).await?;
 
         let static_dir = format!("{}/static", self.configs.root_path.to_str().unwrap());
-        let dist_dir = format!("{}/dist", self.configs.root_path.to_str().unwrap());
-        let notfound_page = Box::new(format!(
-            "{}/dist/pages/_notfound/index.html",
-            self.configs.root_path.to_str().unwrap()
-        ));
+        let dist_path = self.configs.root_path.join(&self.configs.dist_dir);
+        let dist_dir = dist_path.to_str().unwrap().to_owned();
+        let notfound_page = Box::new(format!("{dist_dir}/pages/_notfound/index.html"));
+
+        // Served where the build links the files, which is derived from the same directory
+        let public_path = default_public_path(&self.configs.dist_dir);
+        let public_path = public_path.trim_end_matches('/');
+        if public_path.is_empty() {
+            return Err(anyhow!(
+                "Cannot serve the output directory {:#?} at the root of the site",
+                self.configs.dist_dir
+            ));
+        }
 
         let mut app = RouterMut::from(
             Router::new()
                 .nest_service("/static", ServeDir::new(&static_dir))
-                .nest_service("/dist", ServeDir::new(&dist_dir)),
+                .nest_service(public_path, ServeDir::new(&dist_dir)),
         );
 
         match self.configs.running_type {
@@ -70,10 +92,26 @@ impl Server {
             RunningType::SSR => app.fallback(|| async { Redirect::to("/_notfound") }),
         }
 
-        PagesHandler::new(&mut app, &dist_dir, self.configs.running_type)?.build()?;
+        if let Some(live_reload) = self.configs.live_reload.clone() {
+            app.route(
+                LIVE_RELOAD_PATH,
+                get(move || async move { live_reload.events() }),
+            );
+        }
+
+        PagesHandler::new(
+            &mut app,
+            &dist_dir,
+            self.configs.running_type,
+            self.configs.live_reload.clone(),
+        )?
+        .build()?;
 
         // **Setting up layers**
 
+        // Caching layer
+        CacheControlLayer::setup(dist_path, &mut app);
+
         // Tracing layer
         TracingLayer::setup(
             TracingLayerOptions {
@@ -0,0 +1,106 @@
+use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use axum::response::sse::{Event, KeepAlive, Sse};
+use tokio::sync::broadcast;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+use crate::overlay::ErrorOverlay;
+
+/// The endpoint browsers listen on for reload events.
+pub const LIVE_RELOAD_PATH: &str = "/__metassr/live-reload";
+
+const RELOAD_EVENT: &str = "reload";
+
+/// Connects to the live reload endpoint, and reloads the page once a rebuild is done.
+const LIVE_RELOAD_SCRIPT: &str = r#"<script>new EventSource("/__metassr/live-reload").addEventListener("reload", () => window.location.reload());</script>"#;
+
+/// Shared state between the development server and the rebuilder.
+///
+/// Rebuilds and renders both go through MetaCall, so they take turns using [`LiveReload::lock`].
+/// Once a rebuild is done, the rebuilder reports its result, and every connected browser is
+/// reloaded. While the last build is broken, pages are replaced by an error overlay.
+#[derive(Debug, Clone)]
+pub struct LiveReload {
+    sender: broadcast::Sender<()>,
+    lock: Arc<Mutex<()>>,
+    build_error: Arc<Mutex<Option<ErrorOverlay>>>,
+}
+
+impl LiveReload {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(16);
+        Self {
+            sender,
+            lock: Arc::new(Mutex::new(())),
+            build_error: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Asks connected browsers to reload the page.
+    pub fn reload(&self) {
+        // Sending only fails if no browser is connected.
+        let _ = self.sender.send(());
+    }
+
+    /// Shows `error` instead of the pages until the next successful build.
+    pub fn build_failed(&self, error: &anyhow::Error) {
+        *self.build_error.lock().unwrap() = Some(ErrorOverlay::new("Build failed", error));
+        self.reload();
+    }
+
+    /// Clears the error overlay of a previous failed build, if any.
+    pub fn build_succeeded(&self) {
+        *self.build_error.lock().unwrap() = None;
+        self.reload();
+    }
+
+    /// The error overlay of the last build, if it failed.
+    pub fn build_error(&self) -> Option<ErrorOverlay> {
+        self.build_error.lock().unwrap().clone()
+    }
+
+    /// Locks the project's build output while it's being rebuilt or rendered.
+    pub fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A stream of server-sent events, one for each finished rebuild.
+    pub fn events(&self) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+        let events = BroadcastStream::new(self.sender.subscribe())
+            .map(|_| Ok(Event::default().event(RELOAD_EVENT).data("")));
+        Sse::new(events).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+    }
+}
+
+impl Default for LiveReload {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Injects the live reload script at the end of the page body.
+pub fn inject_script(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(idx) => format!("{}{LIVE_RELOAD_SCRIPT}{}", &html[..idx], &html[idx..]),
+        None => format!("{html}{LIVE_RELOAD_SCRIPT}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn injecting_script() {
+        let html = inject_script("<html><body><p></p></body></html>");
+        assert_eq!(
+            html,
+            format!("<html><body><p></p>{LIVE_RELOAD_SCRIPT}</body></html>")
+        );
+        assert!(inject_script("<p></p>").ends_with(LIVE_RELOAD_SCRIPT));
+    }
+}
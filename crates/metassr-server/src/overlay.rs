@@ -0,0 +1,122 @@
+use anyhow::Error;
+use metassr_build::server::render_exec::RenderError;
+use metassr_bundler::diagnostics::BundlingError;
+use metassr_utils::code_frame::SourceLocation;
+
+/// A development error page, shown instead of the requested page while the project is broken.
+#[derive(Debug, Clone)]
+pub struct ErrorOverlay {
+    title: String,
+    message: String,
+    location: Option<SourceLocation>,
+    code_frame: Option<String>,
+    stack: Option<String>,
+}
+
+impl ErrorOverlay {
+    /// Creates an overlay for `error`. Render errors thrown by a page and bundling errors are
+    /// located in their source file, with a code frame around the failing line.
+    pub fn new(title: &str, error: &Error) -> Self {
+        if let Some(e) = error.downcast_ref::<RenderError>() {
+            let location = e.location();
+            return Self {
+                title: title.to_string(),
+                message: e.message.clone(),
+                code_frame: location.as_ref().and_then(|l| l.code_frame()),
+                location,
+                stack: Some(e.stack.clone()),
+            };
+        }
+
+        match error.downcast_ref::<BundlingError>() {
+            Some(e) => {
+                let location = e.errors.iter().find_map(|e| e.location());
+                Self {
+                    title: title.to_string(),
+                    message: strip_ansi(&e.to_string()),
+                    code_frame: location.as_ref().and_then(|l| l.code_frame()),
+                    location,
+                    stack: None,
+                }
+            }
+            None => Self {
+                title: title.to_string(),
+                message: strip_ansi(&format!("{error:#}")),
+                location: None,
+                code_frame: None,
+                stack: None,
+            },
+        }
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut details = String::new();
+        if let Some(location) = &self.location {
+            details.push_str(&format!(
+                r#"<p class="location">{}</p>"#,
+                escape(&location.to_string())
+            ));
+        }
+        if let Some(code_frame) = &self.code_frame {
+            details.push_str(&format!("<pre>{}</pre>", escape(code_frame)));
+        }
+        if let Some(stack) = &self.stack {
+            details.push_str(&format!(
+                "<details><summary>Stack trace</summary><pre>{}</pre></details>",
+                escape(stack)
+            ));
+        }
+
+        format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="UTF-8">
+<title>{title}</title>
+<style>
+body {{ margin: 0; padding: 2rem; background: #181818; color: #e8e8e8; font-family: ui-monospace, monospace; }}
+h1 {{ color: #ff5555; font-size: 1.4rem; }}
+.message {{ white-space: pre-wrap; font-size: 1rem; }}
+.location {{ color: #8ab4f8; }}
+pre {{ padding: 1rem; background: #222; border-left: 3px solid #ff5555; overflow-x: auto; }}
+footer {{ color: #888; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<pre class="message">{message}</pre>
+{details}
+<footer>This page reloads once the error is fixed.</footer>
+</body>
+</html>"#,
+            title = escape(&self.title),
+            message = escape(&self.message),
+        )
+    }
+}
+
+/// Removes terminal color codes, which bundler messages may contain.
+fn strip_ansi(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip `ESC [ ... <letter>`.
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, fs::read_to_string, path::PathBuf};
+
+/// The number of lines shown before and after the highlighted line of a code frame.
+const CONTEXT_LINES: usize = 2;
+
+/// A position in a source file. Lines and columns start at 1.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceLocation {
+    pub fn new(file: PathBuf, line: usize, column: usize) -> Self {
+        Self { file, line, column }
+    }
+
+    /// Parses the location of a JavaScript stack frame, such as
+    /// `at Page (/app/src/pages/index.jsx:12:5)` or `at file:///app/src/index.js:3:1`.
+    pub fn from_stack_frame(frame: &str) -> Option<Self> {
+        let frame = frame.trim().strip_prefix("at ")?;
+        let location = match frame.strip_suffix(')') {
+            Some(frame) => &frame[frame.rfind('(')? + 1..],
+            None => frame,
+        };
+        let location = location.strip_prefix("file://").unwrap_or(location);
+
+        let mut parts = location.rsplitn(3, ':');
+        let column = parts.next()?.parse().ok()?;
+        let line = parts.next()?.parse().ok()?;
+        let file = parts.next().filter(|file| !file.is_empty())?;
+
+        Some(Self::new(PathBuf::from(file), line, column))
+    }
+
+    /// Reads the source file and renders the code frame around the location.
+    pub fn code_frame(&self) -> Option<String> {
+        let source = read_to_string(&self.file).ok()?;
+        Some(code_frame(&source, self.line, self.column))
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+/// Renders the lines around `line` of `source`, marking the line and pointing at `column`.
+///
+/// ```text
+///   1 | export default function Page() {
+/// > 2 |     throw new Error("oops");
+///     |     ^
+///   3 | }
+/// ```
+pub fn code_frame(source: &str, line: usize, column: usize) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let first = line.saturating_sub(CONTEXT_LINES).max(1);
+    let last = (line + CONTEXT_LINES).min(lines.len());
+    let width = last.to_string().len();
+
+    let mut frame = vec![];
+    for number in first..=last {
+        let code = lines[number - 1];
+        if number == line {
+            frame.push(format!("> {number:>width$} | {code}"));
+            frame.push(format!(
+                "  {:>width$} | {}^",
+                "",
+                " ".repeat(column.saturating_sub(1))
+            ));
+        } else {
+            frame.push(format!("  {number:>width$} | {code}"));
+        }
+    }
+    frame.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_stack_frames() {
+        assert_eq!(
+            SourceLocation::from_stack_frame("    at Page (/app/src/pages/index.jsx:12:5)"),
+            Some(SourceLocation::new("/app/src/pages/index.jsx".into(), 12, 5))
+        );
+        assert_eq!(
+            SourceLocation::from_stack_frame("at file:///app/src/index.js:3:1"),
+            Some(SourceLocation::new("/app/src/index.js".into(), 3, 1))
+        );
+        assert_eq!(SourceLocation::from_stack_frame("Error: oops"), None);
+        assert_eq!(
+            SourceLocation::from_stack_frame("at new Promise (<anonymous>)"),
+            None
+        );
+    }
+
+    #[test]
+    fn rendering_code_frame() {
+        let source = "function Page() {\n    throw new Error(\"oops\");\n}\n";
+
+        assert_eq!(
+            code_frame(source, 2, 5),
+            "  1 | function Page() {\n> 2 |     throw new Error(\"oops\");\n    |     ^\n  3 | }"
+        );
+    }
+}
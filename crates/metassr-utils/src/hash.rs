@@ -0,0 +1,37 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::{fs::read, path::Path};
+
+/// Returns the SHA-256 hash of `content`, as a lowercase hexadecimal string.
+///
+/// Unlike `std`'s hashers, the hash is stable across runs, platforms and Rust versions, so it
+/// can be persisted and compared by later builds.
+pub fn content_hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Returns the [`content_hash`] of a file.
+pub fn file_hash<P: AsRef<Path> + ?Sized>(path: &P) -> Result<String> {
+    Ok(content_hash(&read(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashing_content() {
+        assert_eq!(
+            content_hash(b"metassr"),
+            content_hash("metassr".to_string().as_bytes())
+        );
+        assert_ne!(content_hash(b"metassr"), content_hash(b"metassr "));
+        assert_eq!(
+            content_hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
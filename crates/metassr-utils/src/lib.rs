@@ -38,6 +38,32 @@ pub mod cache_dir;
 /// ```
 pub mod checker;
 
+/// This module locates errors in source files and renders code frames around them, to show
+/// where a build or render error happened.
+///
+/// **Example**
+///
+/// ```rust
+/// use metassr_utils::code_frame::SourceLocation;
+///
+/// let location = SourceLocation::from_stack_frame("at Page (/app/src/pages/index.jsx:12:5)").unwrap();
+/// assert_eq!(location.line, 12);
+/// assert_eq!(location.column, 5);
+/// ```
+pub mod code_frame;
+
+/// This module provides stable content hashes, to detect changed files between builds.
+///
+/// **Example**
+///
+/// ```rust
+/// use metassr_utils::hash::content_hash;
+///
+/// assert_eq!(content_hash(b"page"), content_hash(b"page"));
+/// assert_eq!(content_hash(b"page").len(), 64);
+/// ```
+pub mod hash;
+
 /// This module offers a utility to generate random numbers based on hash values for purposes such as creating random directory names.
 /// This is useful for situations where you need a random integer in your application.
 /// The random value is generated when the `Rand` struct is instantiated.
@@ -1,66 +1,241 @@
-use std::{fmt::Display, str::FromStr};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use super::traits::Exec;
 use anyhow::{anyhow, Result};
 use clap::ValueEnum;
 use metacall::switch;
-use metassr_build::server;
+use metassr_build::{
+    build_graph::{global_hash, BuildGraph},
+    config::Config,
+    env::Env,
+    images::ImageOptimizer,
+    server,
+};
+use metassr_bundler::diagnostics::{format_group, group_by_file, BundlingError};
+use metassr_bundler::options::{self, BundleOptions};
 
-use metassr_build::{client::ClientBuilder, server::ServerSideBuilder, traits::Build};
+use metassr_build::{project::ProjectBuilder, traits::Build};
+use metassr_fs_analyzer::{
+    dist_dir::CLIENT_MANIFEST,
+    src_dir::{special_entries, SourceDir},
+    DirectoryAnalyzer,
+};
 
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use tracing::{error, info};
 
 pub struct Builder {
     out_dir: String,
     _type: BuildingType,
+    mode: Option<BuildMode>,
+    source_maps: Option<SourceMaps>,
+    no_cache: bool,
+    ssg_workers: Option<usize>,
+    ssg_timeout: Option<Duration>,
 }
 
 impl Builder {
-    pub fn new(_type: BuildingType, out_dir: String) -> Self {
-        Self { out_dir, _type }
+    pub fn new(
+        _type: BuildingType,
+        out_dir: String,
+        mode: Option<BuildMode>,
+        source_maps: Option<SourceMaps>,
+    ) -> Self {
+        Self {
+            out_dir,
+            _type,
+            mode,
+            source_maps,
+            no_cache: false,
+            ssg_workers: None,
+            ssg_timeout: None,
+        }
+    }
+
+    /// Bypasses the bundler's persistent cache, and rebuilds every page.
+    pub fn no_cache(mut self, no_cache: bool) -> Self {
+        self.no_cache = no_cache;
+        self
+    }
+
+    /// Sets the number of workers rendering static pages. Defaults to the number of CPU cores.
+    pub fn ssg_workers(mut self, workers: Option<usize>) -> Self {
+        self.ssg_workers = workers;
+        self
+    }
+
+    /// Sets how long a static page can take to render before it fails. Defaults to 60 seconds.
+    pub fn ssg_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.ssg_timeout = timeout;
+        self
+    }
+
+    /// Resolves the bundling options, from the arguments first, then from the project's configuration.
+    fn options(&self) -> Result<BundleOptions> {
+        let config = Config::load("")?;
+        let mode = self
+            .mode
+            .map(Into::into)
+            .or(config.mode)
+            .unwrap_or_default();
+        let source_maps = self.source_maps.map(Into::into).or(config.source_maps);
+        Ok(BundleOptions::new(mode, source_maps)
+            .cache(!self.no_cache)
+            .backend(config.bundler.unwrap_or_default()))
     }
 }
 
 impl Exec for Builder {
     fn exec(&self) -> anyhow::Result<()> {
-        let _metacall = switch::initialize().unwrap();
-        let instant = Instant::now();
-        {
-            let instant = Instant::now();
+        let options = self.options()?;
+        let dist = Path::new(&self.out_dir);
+        // Render scripts read the variables at build time too, when generating static pages.
+        // They're set before any thread is started, such as the image encoders'.
+        let env = Env::load("", options.mode)?;
+        env.apply();
+        let targets = Config::load("")?.browser_targets("", options.mode)?;
 
-            if let Err(e) = ClientBuilder::new("", &self.out_dir)?.build() {
-                error!(
-                    target = "builder",
-                    message = format!("Couldn't build for the client side:  {e}"),
-                );
-                return Err(anyhow!("Couldn't continue building process."));
-            }
+        // Images are optimized first: the `<Image>` component generated from them is an input
+        // of the pages that use it, so pages are rebuilt when their images change.
+        let images = {
+            let instant = Instant::now();
+            let output = ImageOptimizer::new("", &self.out_dir)?
+                .options(Config::load("")?.images.unwrap_or_default())
+                .build()?;
             info!(
                 target = "builder",
-                message = "Client building is completed",
+                message = format!("Optimized {} image(s)", output.manifest.images.len()),
                 time = format!("{}ms", instant.elapsed().as_millis())
             );
+            output
+        };
+
+        let src = SourceDir::new("src").analyze()?;
+        let pages = src.pages();
+        let (_, special_entries::Head(head)) = src.specials()?;
+        let mut shared = vec![head];
+        if let Some(special_entries::Document(document)) = src.document() {
+            shared.push(document);
         }
 
-        {
-            let instant = Instant::now();
+        // Only the pages whose inputs changed since the last build are rebuilt. Public variables
+        // are inlined into every bundle, and static pages may read any variable while they're
+        // rendered, so changing one of them rebuilds every page.
+        let vars: Vec<String> = match self._type {
+            BuildingType::SSG => env.vars().iter().collect::<Vec<_>>(),
+            BuildingType::SSR => env.public().collect(),
+        }
+        .into_iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect();
+        let global = global_hash(
+            "",
+            &format!(
+                "{} {} {} {} {}\n{}",
+                options.mode,
+                options.source_maps(),
+                self._type,
+                options.backend,
+                targets,
+                vars.join("\n")
+            ),
+        );
+        let previous = BuildGraph::load(dist);
+        // Without the cache, every page is rebuilt, whatever the last build recorded.
+        let built_before = !self.no_cache
+            && dist.join("manifest.json").exists()
+            && dist.join(CLIENT_MANIFEST).exists();
+        let stale = built_before
+            .then(|| previous.stale_pages(&global, &pages))
+            .flatten()
+            .filter(|stale| stale.len() < pages.len());
 
-            if let Err(e) = ServerSideBuilder::new("", &self.out_dir, self._type.into())?.build() {
-                error!(
+        if let Some(stale) = &stale {
+            let mut skipped: Vec<&str> = pages
+                .keys()
+                .filter(|page| !stale.contains(*page))
+                .map(|page| page.as_str())
+                .collect();
+            skipped.sort();
+            info!(
+                target = "builder",
+                message = format!(
+                    "Skipping {} unchanged page(s): {}",
+                    skipped.len(),
+                    skipped.join(", ")
+                ),
+            );
+            if stale.is_empty() {
+                info!(
                     target = "builder",
-                    message = format!("Couldn't build for the server side: {e}"),
+                    message = "Nothing to build, the output is up to date"
                 );
-                return Err(anyhow!("Couldn't continue building process."));
+                return Ok(());
             }
+        }
+
+        let _metacall = switch::initialize().unwrap();
+        let instant = Instant::now();
+        info!(
+            target = "builder",
+            message = format!(
+                "Building in {} mode with {} for {} (source maps: {})",
+                options.mode,
+                options.backend,
+                targets,
+                options.source_maps()
+            ),
+        );
+
+        let output = {
+            let instant = Instant::now();
 
+            let mut builder = ProjectBuilder::new("", &self.out_dir, self._type.into())?
+                .options(options)
+                .image_module(&images.module)
+                .browser_targets(targets)
+                .env(env);
+            if let Some(stale) = &stale {
+                builder = builder.pages(stale.clone());
+            }
+            if let Some(workers) = self.ssg_workers {
+                builder = builder.ssg_workers(workers);
+            }
+            if let Some(timeout) = self.ssg_timeout {
+                builder = builder.ssg_timeout(timeout);
+            }
+            let output = match builder.build() {
+                Ok(output) => output,
+                Err(e) => {
+                    report(&e);
+                    return Err(anyhow!("Couldn't continue building process."));
+                }
+            };
             info!(
                 target = "builder",
-                message = "Server building is completed",
+                message = "Client and server building is completed",
                 time = format!("{}ms", instant.elapsed().as_millis())
             );
-        }
+            output
+        };
+
+        // The pages that were just built replace their entries in the graph of the last build.
+        let mut graph = match &stale {
+            Some(_) => previous,
+            None => BuildGraph::new(global),
+        };
+        let built: HashMap<String, PathBuf> = pages
+            .into_iter()
+            .filter(|(page, _)| stale.as_ref().is_none_or(|stale| stale.contains(page)))
+            .collect();
+        graph.record(&built, &[&output.client, &output.server], &shared, dist)?;
+        graph.write(dist)?;
 
         if (_metacall.0)() == 0 {
             info!(
@@ -73,6 +248,28 @@ impl Exec for Builder {
     }
 }
 
+/// Logs a building error. Bundling errors are logged grouped per file.
+fn report(e: &anyhow::Error) {
+    match e.downcast_ref::<BundlingError>() {
+        Some(bundling_error) => {
+            error!(
+                target = "builder",
+                message = format!(
+                    "Couldn't build the project: bundling failed with {} error(s)",
+                    bundling_error.errors.len()
+                ),
+            );
+            for (file, errors) in group_by_file(&bundling_error.errors) {
+                error!(target = "builder", message = format_group(&file, &errors));
+            }
+        }
+        None => error!(
+            target = "builder",
+            message = format!("Couldn't build the project: {e}"),
+        ),
+    }
+}
+
 #[derive(Debug, ValueEnum, PartialEq, Eq, Clone, Copy)]
 pub enum BuildingType {
     /// Static-Site Generation.
@@ -109,3 +306,40 @@ impl FromStr for BuildingType {
         }
     }
 }
+
+#[derive(Debug, ValueEnum, PartialEq, Eq, Clone, Copy)]
+pub enum BuildMode {
+    /// Minified bundles, with unused code eliminated.
+    Production,
+    /// Readable bundles, built as fast as possible.
+    Development,
+}
+
+impl From<BuildMode> for options::BuildMode {
+    fn from(mode: BuildMode) -> Self {
+        match mode {
+            BuildMode::Production => Self::Production,
+            BuildMode::Development => Self::Development,
+        }
+    }
+}
+
+#[derive(Debug, ValueEnum, PartialEq, Eq, Clone, Copy)]
+pub enum SourceMaps {
+    /// No source maps.
+    None,
+    /// Source maps that browsers don't load, e.g. for error trackers.
+    Hidden,
+    /// Source maps referenced by the bundles.
+    Full,
+}
+
+impl From<SourceMaps> for options::SourceMaps {
+    fn from(source_maps: SourceMaps) -> Self {
+        match source_maps {
+            SourceMaps::None => Self::None,
+            SourceMaps::Hidden => Self::Hidden,
+            SourceMaps::Full => Self::Full,
+        }
+    }
+}
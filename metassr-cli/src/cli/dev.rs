@@ -0,0 +1,263 @@
+use anyhow::Result;
+use metacall::switch;
+use metassr_build::{
+    config::Config, env::Env, images::ImageOptimizer, project::ProjectBuilder,
+    server::BuildingType, traits::Build,
+};
+use metassr_bundler::options::{BuildMode, BundleOptions};
+use metassr_fs_analyzer::{src_dir::SourceDir, DirectoryAnalyzer};
+use metassr_server::{LiveReload, RunningType, Server, ServerConfigs};
+use notify::{RecursiveMode, Watcher};
+use std::{
+    collections::HashSet,
+    env::current_dir,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{channel, Receiver},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::runtime::Handle;
+use tracing::{error, info, warn};
+
+use super::traits::AsyncExec;
+
+const DIST_DIR: &str = "dist";
+
+/// Changes that happen within this delay after the first one are rebuilt together.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(100);
+
+pub struct Dev {
+    port: u16,
+    allow_http_debug: bool,
+}
+
+impl Dev {
+    pub fn new(port: u16, allow_http_debug: bool) -> Self {
+        Self {
+            port,
+            allow_http_debug,
+        }
+    }
+
+    /// The variables render scripts and loaders read from the process' environment. They're
+    /// applied before the runtime starts.
+    pub fn env(&self) -> Result<Env> {
+        Env::load("", BuildMode::Development)
+    }
+}
+
+impl AsyncExec for Dev {
+    async fn exec(&self) -> Result<()> {
+        let _metacall = switch::initialize().unwrap();
+        let root = current_dir()?;
+        let live_reload = LiveReload::new();
+
+        // A broken project is still served, showing the build error until it's fixed.
+        if let Err(e) = build(None, 0).await {
+            error!(target = "dev", message = format!("{e:#}"));
+            live_reload.build_failed(&e);
+        }
+        let (tx, rx) = channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        for dir in ["src", "static"] {
+            if root.join(dir).exists() {
+                watcher.watch(&root.join(dir), RecursiveMode::Recursive)?;
+            }
+        }
+
+        let rebuilder = Rebuilder::new(&root, live_reload.clone())?;
+        thread::spawn(move || rebuilder.watch(rx));
+
+        let server_configs = ServerConfigs {
+            port: self.port,
+            _enable_http_logging: self.allow_http_debug,
+            root_path: root,
+            dist_dir: PathBuf::from(DIST_DIR),
+            running_type: RunningType::SSR,
+            live_reload: Some(live_reload),
+        };
+
+        info!(
+            target = "dev",
+            message = "Watching for changes in `src` and `static`"
+        );
+
+        Server::new(server_configs).run().await?;
+        drop(watcher);
+        Ok(())
+    }
+}
+
+/// Builds the client and server sides of the project in development mode, limited to `pages`
+/// if given. The configuration and `.env` files are read on every build, so changes to its
+/// source maps, bundler, images, browser targets and public variables apply without a restart.
+///
+/// Each build of the process has its own `revision`, so the server loads the rebuilt render
+/// scripts instead of the ones it loaded before.
+///
+/// The bundling is awaited rather than blocked on, as builds run on the server's runtime.
+async fn build(pages: Option<HashSet<String>>, revision: u64) -> Result<()> {
+    let instant = Instant::now();
+    let config = Config::load("")?;
+    let options = BundleOptions::new(BuildMode::Development, config.source_maps)
+        .backend(config.bundler.unwrap_or_default());
+    let env = Env::load("", BuildMode::Development)?;
+    let targets = config.browser_targets("", BuildMode::Development)?;
+    let images = ImageOptimizer::new("", DIST_DIR)?
+        .options(config.images.unwrap_or_default())
+        .build()?;
+
+    let mut builder = ProjectBuilder::new("", DIST_DIR, BuildingType::ServerSideRendering)?
+        .options(options)
+        .image_module(&images.module)
+        .browser_targets(targets)
+        .revision(revision)
+        .env(env);
+    if let Some(pages) = pages {
+        builder = builder.pages(pages);
+    }
+
+    if let Err(e) = builder.bundle().await {
+        return Err(e.context("Couldn't build the project"));
+    }
+
+    info!(
+        target = "dev",
+        message = "Building is completed",
+        time = format!("{}ms", instant.elapsed().as_millis())
+    );
+    Ok(())
+}
+
+/// Rebuilds the pages affected by the changed files, then reloads the browsers.
+struct Rebuilder {
+    src_dir: PathBuf,
+    static_dir: PathBuf,
+    /// Routes registered when the server started. New pages need a restart to be served.
+    routes: HashSet<String>,
+    live_reload: LiveReload,
+    /// The revision of the last build, the first one being 0.
+    revision: AtomicU64,
+    /// The runtime of the server, the rebuilds run on.
+    runtime: Handle,
+}
+
+impl Rebuilder {
+    fn new(root: &Path, live_reload: LiveReload) -> Result<Self> {
+        let src_dir = root.join("src");
+        let routes = SourceDir::new(&src_dir)
+            .analyze()?
+            .pages()
+            .into_keys()
+            .collect();
+
+        Ok(Self {
+            src_dir,
+            static_dir: root.join("static"),
+            routes,
+            live_reload,
+            revision: AtomicU64::new(0),
+            runtime: Handle::current(),
+        })
+    }
+
+    fn watch(&self, rx: Receiver<notify::Result<notify::Event>>) {
+        while let Ok(event) = rx.recv() {
+            let mut changed = HashSet::new();
+            let mut collect = |event: notify::Result<notify::Event>| match event {
+                Ok(event) if !event.kind.is_access() => changed.extend(event.paths),
+                Ok(_) => (),
+                Err(e) => warn!(target = "dev", message = format!("Watch error: {e}")),
+            };
+
+            collect(event);
+            while let Ok(event) = rx.recv_timeout(DEBOUNCE_DELAY) {
+                collect(event);
+            }
+
+            if changed.is_empty() {
+                continue;
+            }
+            if let Err(e) = self.rebuild(&changed) {
+                error!(target = "dev", message = format!("{e:#}"));
+                self.live_reload.build_failed(&e);
+            }
+        }
+    }
+
+    fn rebuild(&self, changed: &HashSet<PathBuf>) -> Result<()> {
+        // Images under `static/images` are optimized by the build, like the sources.
+        let images = self.static_dir.join("images");
+        let sources: Vec<&PathBuf> = changed
+            .iter()
+            .filter(|path| !path.starts_with(&self.static_dir) || path.starts_with(&images))
+            .collect();
+
+        // Other static files are served as is, so only the browsers need a reload.
+        if sources.is_empty() {
+            self.live_reload.reload();
+            return Ok(());
+        }
+
+        // After a failed build, everything is rebuilt, so fixing one page doesn't hide the
+        // errors of the others.
+        let affected = match self.live_reload.build_error() {
+            Some(_) => None,
+            None => self.affected_pages(&sources)?,
+        };
+
+        if let Some(routes) = &affected {
+            if routes.is_empty() {
+                return Ok(());
+            }
+            for route in routes.difference(&self.routes) {
+                warn!(
+                    target = "dev",
+                    message =
+                        format!("New page `{route}` needs a restart of `metassr dev` to be served")
+                );
+            }
+        }
+
+        {
+            let _lock = self.live_reload.lock();
+            let revision = self.revision.fetch_add(1, Ordering::Relaxed) + 1;
+            self.runtime.block_on(build(affected, revision))?;
+        }
+        self.live_reload.build_succeeded();
+        Ok(())
+    }
+
+    /// Returns the routes of the pages affected by the changed `sources`, or `None` if every
+    /// page is affected.
+    fn affected_pages(&self, sources: &[&PathBuf]) -> Result<Option<HashSet<String>>> {
+        let src = SourceDir::new(&self.src_dir).analyze()?;
+        let pages = src.pages();
+        let mut affected = Some(HashSet::new());
+
+        for &path in sources {
+            let set = match affected.as_mut() {
+                Some(set) => set,
+                None => break,
+            };
+
+            if let Some((route, _)) = pages.iter().find(|(_, page)| *page == path) {
+                set.insert(route.clone());
+            } else if path.file_stem().is_some_and(|stem| stem == "_layout") {
+                set.extend(
+                    pages
+                        .keys()
+                        .filter(|route| src.layouts_for(route).contains(path))
+                        .cloned(),
+                );
+            } else {
+                // Shared modules (`_app`, components, styles...) may be used by every page.
+                affected = None;
+            }
+        }
+        Ok(affected)
+    }
+}
@@ -1,10 +1,12 @@
 mod builder;
 mod creator;
+mod dev;
 mod runner;
 pub mod traits;
 
 pub use builder::*;
 pub use creator::*;
+pub use dev::*;
 pub use runner::*;
 
 use clap::{command, Parser, Subcommand, ValueEnum};
@@ -55,6 +57,27 @@ pub enum Commands {
         /// The type of build to perform. Choose between SSR (Server-Side Rendering) and SSG (Static Site Generation).
         #[arg(short = 't', long = "type", default_value_t = BuildingType::SSR)]
         build_type: BuildingType,
+
+        /// The build mode. Production builds are minified, development builds are faster and easier to debug.
+        /// Defaults to the `mode` of `metassr.config.json`, or production.
+        #[arg(long)]
+        mode: Option<BuildMode>,
+
+        /// The source maps of the client bundles. Defaults to hidden in production and full in development.
+        #[arg(long)]
+        source_maps: Option<SourceMaps>,
+
+        /// Bypass the bundler's persistent cache, stored in `<out-dir>/cache/rspack`, and rebuild every page.
+        #[arg(long)]
+        no_cache: bool,
+
+        /// The number of workers rendering the pages of an SSG build in parallel. Defaults to the number of CPU cores.
+        #[arg(long)]
+        ssg_workers: Option<usize>,
+
+        /// How long a page of an SSG build can take to render, in seconds, before it fails. Defaults to 60 seconds.
+        #[arg(long)]
+        ssg_timeout: Option<u64>,
     },
 
     /// Runs the Server-Side Rendered (SSR) application.
@@ -66,6 +89,17 @@ pub enum Commands {
         /// Serve the generated static site directly.
         #[arg(long)]
         serve: bool,
+
+        /// The output directory of the build to serve. Its files are served under `/<out-dir name>/`, the URL the build links them with.
+        #[arg(long, default_value_t = String::from("dist"))]
+        out_dir: String,
+    },
+
+    /// Runs the application in development mode, rebuilding changed pages and reloading the browser.
+    Dev {
+        /// The port number on which the HTTP server will run.
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
     },
 
     /// Creates a new MetaSSR project with the specified template.
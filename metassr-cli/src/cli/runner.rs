@@ -1,7 +1,8 @@
 use anyhow::Result;
 use metacall::switch;
+use metassr_build::{config::Config, env::Env};
 use metassr_server::{RunningType, Server, ServerConfigs};
-use std::env::current_dir;
+use std::{env::current_dir, path::PathBuf};
 use tracing::info;
 
 use super::traits::AsyncExec;
@@ -9,17 +10,25 @@ use super::traits::AsyncExec;
 pub struct Runner {
     port: u16,
     is_served: bool,
+    out_dir: String,
     allow_http_debug: bool,
 }
 
 impl Runner {
-    pub fn new(port: u16, is_served: bool, allow_http_debug: bool) -> Self {
+    pub fn new(port: u16, is_served: bool, out_dir: String, allow_http_debug: bool) -> Self {
         Self {
             port,
             is_served,
+            out_dir,
             allow_http_debug,
         }
     }
+
+    /// The variables render scripts and loaders read from the process' environment, in the
+    /// mode the project was configured with. They're applied before the runtime starts.
+    pub fn env(&self) -> Result<Env> {
+        Env::load("", Config::load("")?.mode.unwrap_or_default())
+    }
 }
 impl AsyncExec for Runner {
     async fn exec(&self) -> Result<()> {
@@ -33,7 +42,9 @@ impl AsyncExec for Runner {
             port: self.port,
             _enable_http_logging: self.allow_http_debug,
             root_path: current_dir()?,
+            dist_dir: PathBuf::from(&self.out_dir),
             running_type,
+            live_reload: None,
         };
 
         info!("Running your web application on {:?} mode", running_type);
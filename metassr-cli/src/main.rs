@@ -11,12 +11,19 @@ use anyhow::Result;
 use std::{
     env::{set_current_dir, set_var},
     path::Path,
+    time::Duration,
 };
 
+use tokio::runtime::{self, Runtime};
 use tracing_subscriber::{prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt};
 
-#[tokio::main]
-async fn main() -> Result<()> {
+/// The runtime of the commands serving the project. It's only started once the environment of
+/// the process is set, as setting variables while other threads run isn't safe.
+fn runtime() -> Result<Runtime> {
+    Ok(runtime::Builder::new_multi_thread().enable_all().build()?)
+}
+
+fn main() -> Result<()> {
     let args = Args::parse();
 
     let allow_metacall_debug =
@@ -49,13 +56,31 @@ async fn main() -> Result<()> {
         Commands::Build {
             out_dir,
             build_type,
+            mode,
+            source_maps,
+            no_cache,
+            ssg_workers,
+            ssg_timeout,
+        } => {
+            cli::Builder::new(build_type, out_dir, mode, source_maps)
+                .no_cache(no_cache)
+                .ssg_workers(ssg_workers)
+                .ssg_timeout(ssg_timeout.map(Duration::from_secs))
+                .exec()?;
+        }
+        Commands::Run {
+            port,
+            serve,
+            out_dir,
         } => {
-            cli::Builder::new(build_type, out_dir).exec()?;
+            let runner = cli::Runner::new(port, serve, out_dir, allow_http_debug);
+            runner.env()?.apply();
+            runtime()?.block_on(runner.exec())?;
         }
-        Commands::Run { port, serve } => {
-            cli::Runner::new(port, serve, allow_http_debug)
-                .exec()
-                .await?;
+        Commands::Dev { port } => {
+            let dev = cli::Dev::new(port, allow_http_debug);
+            dev.env()?.apply();
+            runtime()?.block_on(dev.exec())?;
         }
         Commands::Create {
             project_name,
@@ -0,0 +1,8 @@
+// A page rendering fine, but throwing once the render function has returned.
+exports.render_1 = () => {
+    setTimeout(() => {
+        throw new Error("Thrown asynchronously");
+    });
+    return JSON.stringify({ ok: "<h1>Async throw</h1>" });
+};
+exports.page_head_1 = () => JSON.stringify({ ok: {} });
@@ -0,0 +1,3 @@
+// A page exiting the process while it's rendered.
+exports.render_1 = () => process.exit(1);
+exports.page_head_1 = () => JSON.stringify({ ok: {} });
@@ -0,0 +1,5 @@
+// A page that never returns.
+exports.render_1 = () => {
+    for (;;) {}
+};
+exports.page_head_1 = () => JSON.stringify({ ok: {} });
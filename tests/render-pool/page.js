@@ -0,0 +1,3 @@
+// A page rendering as expected, with the guarded results of the render scripts.
+exports.render_1 = () => JSON.stringify({ ok: "<h1>Page</h1>" });
+exports.page_head_1 = () => JSON.stringify({ ok: {} });
@@ -1,6 +1,12 @@
 import React, { useState, ReactNode } from 'react';
 import { renderToString } from 'react-dom/server';
 
+// Uses a hook, so the head script renders it instead of walking it.
+function Generator() {
+    const [name] = useState('MetaSSR');
+    return <meta name="generator" content={name} />;
+}
+
 export default function Head() {
     return (
         <>
@@ -9,6 +15,7 @@ export default function Head() {
             <meta name="keywords" content="HTML, CSS, JavaScript" />
             <meta name="author" content="John Doe" />
             <title>My website</title>
+            <Generator />
         </>
     );
 }
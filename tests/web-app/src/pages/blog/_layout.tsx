@@ -0,0 +1,15 @@
+import React, { ReactNode } from 'react';
+
+export default function BlogLayout({ children }: { children: ReactNode }) {
+	return (
+		<section>
+			<div className="text-sm text-gray-500">MetaSSR Blog</div>
+			{children}
+		</section>
+	);
+}
+
+export const metadata = {
+	title: "MetaSSR Blog",
+	author: "MetaSSR",
+};
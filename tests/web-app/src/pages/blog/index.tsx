@@ -7,4 +7,23 @@ export default function Blog() {
         </div>
     )
 
-}
\ No newline at end of file
+}
+export const metadata = {
+    title: "Blog",
+    description: "A cool blog built with MetaSSR",
+    openGraph: { title: "Blog", type: "website" },
+};
+
+function Keywords() {
+    return <meta name="keywords" content="blog, metassr" />;
+}
+
+// Tags with the same key replace each other.
+export const head = (
+    <>
+        <Keywords />
+        <meta name="generator" content="MetaSSR Blog" />
+        <link key="feed" rel="alternate" href="/blog/feed.xml" />
+        <link key="feed" rel="alternate" href="/blog/rss.xml" />
+    </>
+);